@@ -9,13 +9,22 @@
 //! - Multi-asset liquidity pool management
 //! - Sophisticated yield farming with dynamic APY
 //! - Automated reward distribution mechanisms
-//! - Liquidity mining incentives
-//! - Impermanent loss protection
+//! - Liquidity mining incentives for staked LP shares, boosted for shares locked for 1 to 12 months
+//! - Impermanent loss protection, paid from a per-pool fund fed by a slice of swap fees
 //! - Advanced fee sharing algorithms
+//! 
+//! This contract is the custodian of pool reserves: the swap contract prices routes
+//! against `get_reserves` and settles every hop through `swap`, so swap fees stay
+//! in the pool and accrue to LPs.
+//! 
+//! Each pool's LP share is a SEP-41 token deployed from the `lp-token` contract when
+//! the pool is created; `add_liquidity` mints it and `remove_liquidity` burns it.
+//! Only shares staked or locked with this contract earn mining rewards, so a share
+//! earns for exactly one position however often it is transferred.
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, contracterror, contractmeta,
-    Address, Env, String,
+    contract, contractclient, contractimpl, contracttype, contracterror, contractmeta,
+    Address, BytesN, Env, String, Vec, token::TokenClient, xdr::ToXdr,
 };
 
 // Contract metadata
@@ -27,9 +36,27 @@ contractmeta!(
 contractmeta!(key = "Version", val = "2.0.0");
 
 /// Liquidity constants
-const BASE_APY: u32 = 500;                  // 5% base APY
 const MAX_APY: u32 = 5000;                  // 50% max APY
 const MIN_LIQUIDITY_AMOUNT: i128 = 1_000_000; // 0.1 XLM minimum
+const MINIMUM_LIQUIDITY: i128 = 1_000;      // LP shares locked forever on a pool's first deposit
+const FEE_GROWTH_PRECISION: i128 = 1_000_000_000_000; // Scale of per-share fee growth
+const IL_VESTING_PERIOD: u64 = 8_640_000;  // 100 days to full IL coverage
+const BASIS_POINTS: i128 = 10_000;
+const REWARD_PRECISION: i128 = 1_000_000_000_000; // Scale of acc_reward_per_share
+const MAX_ACTIVE_CAMPAIGNS: u32 = 5;        // Unfinished reward campaigns per pool
+const MAX_POOL_CAMPAIGNS: u32 = 10;         // Campaigns tracked per pool, finished ones included
+const MAX_CAMPAIGN_DURATION: u64 = 31_536_000; // Campaigns emit for at most a year
+const CAMPAIGN_CLAIM_PERIOD: u64 = 2_592_000; // Finished campaigns stay claimable for 30 days
+const LOCK_TIERS: [(u32, u64, u32); 4] = [  // (months, duration, reward multiplier in basis points)
+    (1, 2_592_000, 11_000),
+    (3, 7_776_000, 12_500),
+    (6, 15_552_000, 15_000),
+    (12, 31_536_000, 20_000),
+];
+const MAX_EARLY_EXIT_PENALTY: i128 = 1_000; // Up to 10% of locked shares burned on early exit
+const SECONDS_PER_DAY: u64 = 86_400;
+const SECONDS_PER_YEAR: i128 = 31_536_000;
+const FEE_SNAPSHOT_DAYS: u64 = 7;           // Daily fee snapshots kept per pool (trailing APY window)
 
 
 /// Contract errors
@@ -47,6 +74,15 @@ pub enum LiquidityError {
     PoolCapExceeded = 8,
     ImpermanentLossExceeded = 9,
     RewardClaimFailed = 10,
+    LpTokenNotConfigured = 11,
+    SlippageExceeded = 12,
+    DeadlineExpired = 13,
+    PriceUnavailable = 14,
+    InvalidLockPeriod = 15,
+    PositionLocked = 16,
+    AlreadyInitialized = 17,
+    RewardTokenNotAllowed = 18,
+    PoolAlreadyExists = 19,
 }
 
 /// Liquidity pool information
@@ -65,6 +101,12 @@ pub struct LiquidityPool {
     pub reserve_b: i128,
     /// Total LP tokens issued
     pub total_lp_tokens: i128,
+    /// SEP-41 LP share token
+    pub lp_token: Address,
+    /// Cumulative token A swap fees per LP share (scaled by FEE_GROWTH_PRECISION)
+    pub fee_growth_a: i128,
+    /// Cumulative token B swap fees per LP share (scaled by FEE_GROWTH_PRECISION)
+    pub fee_growth_b: i128,
     /// Current APY (basis points)
     pub current_apy: u32,
     /// Pool creation timestamp
@@ -93,32 +135,179 @@ pub struct LiquidityPosition {
     pub initial_token_a: i128,
     /// Initial token B amount
     pub initial_token_b: i128,
-    /// Accumulated rewards
+    /// Reward tokens paid out
     pub accumulated_rewards: i128,
     /// Last reward claim
     pub last_reward_claim: u64,
-    /// Impermanent loss tracking
+    /// Realized impermanent loss (USD, 8 decimals)
     pub impermanent_loss: i128,
+    /// Average pool fee growth A at deposit time
+    pub fee_growth_entry_a: i128,
+    /// Average pool fee growth B at deposit time
+    pub fee_growth_entry_b: i128,
+    /// Token A fees realized on withdrawals
+    pub fees_earned_a: i128,
+    /// Token B fees realized on withdrawals
+    pub fees_earned_b: i128,
+    /// Impermanent loss compensation received (USD, 8 decimals)
+    pub il_compensated: i128,
+    /// LP shares staked with this contract, locked shares included
+    pub reward_stake: i128,
+    /// Reward stake with the lock boost applied
+    pub reward_weight: i128,
+    /// `reward_weight * acc_reward_per_share` already accounted for
+    pub reward_debt: i128,
+    /// Rewards settled but not yet claimed
+    pub unclaimed_rewards: i128,
+    /// LP shares held by this contract under a lock
+    pub locked_shares: i128,
+    /// Lock end timestamp
+    pub lock_until: u64,
+    /// Length of the current lock
+    pub lock_duration: u64,
+    /// Reward multiplier of the current lock (basis points)
+    pub lock_multiplier: u32,
+}
+
+/// Impermanent loss protection settings
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProtectionConfig {
+    /// Share of each swap fee routed to the pool's protection fund (basis points)
+    pub fee_share_bps: u32,
+    /// Share of measured IL covered once fully vested (basis points)
+    pub max_coverage_bps: u32,
+    /// Position age at which coverage is fully vested (seconds)
+    pub vesting_period: u64,
+    /// Most compensation paid to one position over its lifetime (USD, 8 decimals)
+    pub max_payout_usd: i128,
+}
+
+/// Protection fund balances of a pool
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProtectionFund {
+    /// Token A held for compensation
+    pub balance_a: i128,
+    /// Token B held for compensation
+    pub balance_b: i128,
+    /// Total compensation paid (USD, 8 decimals)
+    pub total_compensated_usd: i128,
+}
+
+/// Protection fund solvency
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProtectionFundStatus {
+    /// Pool ID
+    pub pool_id: String,
+    /// Token A held for compensation
+    pub balance_a: i128,
+    /// Token B held for compensation
+    pub balance_b: i128,
+    /// Fund value at current prices (USD, 8 decimals)
+    pub fund_value_usd: i128,
+    /// Pool liquidity at current prices (USD, 8 decimals)
+    pub pool_value_usd: i128,
+    /// Fund value relative to pool liquidity (basis points)
+    pub coverage_ratio_bps: u32,
+    /// Total compensation paid (USD, 8 decimals)
+    pub total_compensated_usd: i128,
+}
+
+/// Profit and loss of a liquidity position
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PositionPnl {
+    /// Token A fee income, realized and pending
+    pub fee_income_a: i128,
+    /// Token B fee income, realized and pending
+    pub fee_income_b: i128,
+    /// Fee income at current prices (USD, 8 decimals)
+    pub fee_income_usd: i128,
+    /// Pool emission tokens claimed and claimable
+    pub rewards: i128,
+    /// Pool emissions and campaign rewards, claimed and claimable, at current prices
+    /// (USD, 8 decimals); unpriced reward tokens count as zero
+    pub rewards_usd: i128,
+    /// Realized plus unrealized impermanent loss (USD, 8 decimals)
+    pub impermanent_loss_usd: i128,
+    /// Impermanent loss compensation received (USD, 8 decimals)
+    pub il_compensation_usd: i128,
+    /// Value of the remaining deposit had it been held (USD, 8 decimals)
+    pub hold_value_usd: i128,
+    /// Current redeemable value of the remaining position (USD, 8 decimals)
+    pub position_value_usd: i128,
+}
+
+/// Liquidity mining emissions of a pool
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolRewards {
+    /// Token paid as reward, once configured
+    pub reward_token: Option<Address>,
+    /// Reward tokens emitted per second
+    pub emission_rate: i128,
+    /// Rewards accrued per unit of reward weight (scaled by REWARD_PRECISION)
+    pub acc_reward_per_share: i128,
+    /// Last accrual timestamp
+    pub last_update: u64,
+    /// Reward weight of all staked LP shares, lock boosts included
+    pub total_staked: i128,
+    /// Funded rewards not yet emitted
+    pub reward_reserve: i128,
+}
+
+/// Partner reward campaign on a pool. Kept apart from `RewardDistribution`, which only
+/// aggregates payouts per pool and start time: a campaign also needs its creator, token,
+/// schedule and per-share accumulator. Claims feed the matching distribution record.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RewardCampaign {
+    /// Campaign ID
+    pub id: u32,
+    /// Pool ID
+    pub pool_id: String,
+    /// Funder, who may withdraw what was never emitted
+    pub creator: Address,
+    /// Token paid as reward
+    pub reward_token: Address,
+    /// Rewards funded
+    pub total_amount: i128,
+    /// Emission start
+    pub start: u64,
+    /// Emission end
+    pub end: u64,
+    /// Rewards accrued per unit of reward weight (scaled by REWARD_PRECISION)
+    pub acc_reward_per_share: i128,
+    /// Last accrual timestamp
+    pub last_update: u64,
+    /// Rewards emitted to stakers so far
+    pub emitted: i128,
+    /// Rewards paid out to stakers so far
+    pub claimed: i128,
+}
+
+/// A position's standing in one campaign
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CampaignPosition {
+    /// `reward_weight * acc_reward_per_share` already accounted for
+    pub reward_debt: i128,
+    /// Rewards settled but not yet claimed
+    pub unclaimed: i128,
+    /// Rewards claimed
+    pub claimed: i128,
 }
 
-/// Yield farming rewards
+/// Reward tokens paid by a claim
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct YieldFarmRewards {
-    /// SWAVE token rewards
-    pub swave_rewards: i128,
-    /// LP token rewards
-    pub lp_rewards: i128,
-    /// Fee rewards
-    pub fee_rewards: i128,
-    /// Total rewards value (USD)
-    pub total_value_usd: i128,
-    /// APY calculation
-    pub effective_apy: u32,
-    /// Reward period
-    pub period_start: u64,
-    /// Reward period end
-    pub period_end: u64,
+pub struct RewardPayout {
+    /// Reward token
+    pub reward_token: Address,
+    /// Amount transferred
+    pub amount: i128,
 }
 
 /// Reward distribution event
@@ -139,6 +328,60 @@ pub struct RewardDistribution {
     pub new_apy: u32,
 }
 
+/// Swap fees and volume of a pool over one day (token units)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DailySnapshot {
+    /// Day number (timestamp / SECONDS_PER_DAY)
+    pub day: u64,
+    /// Token A fees charged
+    pub fees_a: i128,
+    /// Token B fees charged
+    pub fees_b: i128,
+    /// Token A swapped in
+    pub volume_a: i128,
+    /// Token B swapped in
+    pub volume_b: i128,
+}
+
+/// Amounts actually taken by a deposit and the LP shares minted for them
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LiquidityDeposit {
+    /// Token A deposited
+    pub amount_a: i128,
+    /// Token B deposited
+    pub amount_b: i128,
+    /// LP shares minted to the provider
+    pub lp_tokens: i128,
+}
+
+/// Pool reserves exposed to the swap contract
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolReserves {
+    /// Token A contract
+    pub token_a: Address,
+    /// Token B contract
+    pub token_b: Address,
+    /// Reserve A
+    pub reserve_a: i128,
+    /// Reserve B
+    pub reserve_b: i128,
+}
+
+/// Tracked reserves of a token compared with the contract's actual balance
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReserveReconciliation {
+    /// Token contract
+    pub token: Address,
+    /// Sum of pool reserves in this token
+    pub tracked_reserves: i128,
+    /// Token balance actually held by the contract
+    pub actual_balance: i128,
+}
+
 /// Storage keys
 #[contracttype]
 #[derive(Clone)]
@@ -151,28 +394,106 @@ pub enum DataKey {
     Distribution(String, u64), // (pool_id, timestamp)
     /// Global pool stats
     GlobalStats,
+    /// Sum of all pool reserves held in a token
+    TokenReserves(Address),
     /// Admin address
     Admin,
     /// Reward configuration
     RewardConfig,
+    /// Swap contract allowed to settle trades against pool reserves
+    SwapContract,
+    /// Wasm hash deployed as each pool's LP token
+    LpTokenWasm,
+    /// Price oracle contract
+    Oracle,
+    /// Oracle asset symbol for a token
+    PriceFeed(Address),
+    /// Impermanent loss protection settings
+    ProtectionConfig,
+    /// Impermanent loss protection fund of a pool
+    ProtectionFund(String),
+    /// Liquidity mining emissions of a pool
+    PoolRewards(String),
+    /// Next reward campaign ID
+    NextCampaignId,
+    /// Reward campaign
+    Campaign(u32),
+    /// Campaigns still claimable on a pool
+    PoolCampaigns(String),
+    /// Position standing in a campaign
+    CampaignPosition(u32, Address), // (campaign_id, provider)
+    /// Minimum funding of a campaign paying a whitelisted reward token
+    CampaignToken(Address),
+    /// Ring buffer of a pool's daily fee snapshots, indexed by day % FEE_SNAPSHOT_DAYS
+    FeeSnapshots(String),
+    /// A pool's last counted contribution to global stats
+    PoolValue(String), // (tvl_usd, apy)
+    /// Sum of pool TVL times pool APY, for the TVL-weighted average APY
+    TvlWeightedApy,
+    /// Number of pools in which a provider has a nonzero position
+    ProviderPools(Address),
 }
 
 /// Global liquidity statistics
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct GlobalLiquidityStats {
-    /// Total value locked (USD)
+    /// Total value locked at oracle prices (USD, 8 decimals); pools without prices count as zero
     pub total_tvl_usd: i128,
-    /// Total pools
+    /// Pools created
     pub total_pools: u32,
-    /// Active liquidity providers
+    /// Providers with at least one nonzero position
     pub active_providers: u32,
-    /// Total rewards distributed
+    /// Rewards claimed, valued at claim time (USD, 8 decimals); unpriced reward tokens are not counted
     pub total_rewards_distributed: i128,
-    /// Average APY across pools
+    /// TVL-weighted average pool APY (basis points)
     pub average_apy: u32,
 }
 
+/// Oracle price source (mirrors the oracle contract)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PriceSource {
+    Chainlink,
+    RedStone,
+    StellarDEX,
+    Pyth,
+    Manual,
+}
+
+/// Aggregated oracle price (mirrors the oracle contract)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AggregatedPrice {
+    /// Asset symbol
+    pub asset: String,
+    /// Weighted average price (USD, 8 decimals)
+    pub price: i128,
+    /// Price timestamp
+    pub timestamp: u64,
+    /// Contributing sources
+    pub sources: Vec<PriceSource>,
+    /// Price confidence score
+    pub confidence_score: u32,
+    /// TWAP price
+    pub twap_price: i128,
+    /// Price deviation from previous
+    pub deviation: u32,
+}
+
+/// Oracle contract interface
+#[contractclient(name = "OracleClient")]
+pub trait OracleContractInterface {
+    fn get_price(env: Env, asset: String) -> AggregatedPrice;
+}
+
+/// LP share token interface
+#[contractclient(name = "LpTokenClient")]
+pub trait LpTokenInterface {
+    fn initialize(env: Env, admin: Address, pool_id: String);
+    fn mint(env: Env, to: Address, amount: i128);
+}
+
 /// SWAVE Ultimate Liquidity Management Contract
 #[contract]
 pub struct UltimateLiquidityContract;
@@ -185,63 +506,99 @@ impl UltimateLiquidityContract {
         env: Env,
         admin: Address,
     ) -> Result<(), LiquidityError> {
+        if env.storage().instance().has(&DataKey::Admin) {
+            return Err(LiquidityError::AlreadyInitialized);
+        }
+        admin.require_auth();
+        
         // Set admin
         env.storage().instance().set(&DataKey::Admin, &admin);
         
         // Initialize global stats
-        let stats = GlobalLiquidityStats {
-            total_tvl_usd: 0,
-            total_pools: 0,
-            active_providers: 0,
-            total_rewards_distributed: 0,
-            average_apy: BASE_APY,
-        };
-        
+        let stats = Self::load_global_stats(&env);
         env.storage().instance().set(&DataKey::GlobalStats, &stats);
         
         Ok(())
     }
 
-    /// Add liquidity to pool
+    /// Add liquidity to pool.
+    /// Only the amounts matching the pool ratio are taken; the excess stays with the provider.
+    #[allow(clippy::too_many_arguments)]
     pub fn add_liquidity(
         env: Env,
         provider: Address,
         pool_id: String,
-        token_a_amount: i128,
-        token_b_amount: i128,
-    ) -> Result<i128, LiquidityError> {
+        amount_a_desired: i128,
+        amount_b_desired: i128,
+        amount_a_min: i128,
+        amount_b_min: i128,
+        deadline: u64,
+    ) -> Result<LiquidityDeposit, LiquidityError> {
         provider.require_auth();
         
+        if env.ledger().timestamp() > deadline {
+            return Err(LiquidityError::DeadlineExpired);
+        }
+        
         // Validate amounts
-        if token_a_amount < MIN_LIQUIDITY_AMOUNT || token_b_amount < MIN_LIQUIDITY_AMOUNT {
+        if amount_a_desired < MIN_LIQUIDITY_AMOUNT || amount_b_desired < MIN_LIQUIDITY_AMOUNT {
+            return Err(LiquidityError::InvalidAmount);
+        }
+        if amount_a_min < 0 || amount_b_min < 0 || amount_a_min > amount_a_desired || amount_b_min > amount_b_desired {
             return Err(LiquidityError::InvalidAmount);
         }
         
-        // Get or create pool
-        // For testnet deployment: simplified pool ID handling
-        let mut pool = Self::get_or_create_pool(&env, "XLM_USDC", token_a_amount, token_b_amount)?;
+        let mut pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        if !pool.active {
+            return Err(LiquidityError::PoolNotFound);
+        }
         
-        // Calculate LP tokens to issue
+        // Match the pool ratio, then calculate LP tokens to issue
+        let (token_a_amount, token_b_amount) = Self::calculate_deposit_amounts(&pool, amount_a_desired, amount_b_desired)?;
+        if token_a_amount < amount_a_min || token_b_amount < amount_b_min {
+            return Err(LiquidityError::SlippageExceeded);
+        }
         let lp_tokens = Self::calculate_lp_tokens(&pool, token_a_amount, token_b_amount)?;
+        let locked = if pool.total_lp_tokens == 0 { MINIMUM_LIQUIDITY } else { 0 };
+        
+        // Take custody of the deposited tokens
+        let contract = env.current_contract_address();
+        TokenClient::new(&env, &pool.token_a).transfer(&provider, &contract, &token_a_amount);
+        TokenClient::new(&env, &pool.token_b).transfer(&provider, &contract, &token_b_amount);
+        Self::adjust_token_reserves(&env, &pool.token_a, token_a_amount);
+        Self::adjust_token_reserves(&env, &pool.token_b, token_b_amount);
         
         // Update pool reserves
         pool.reserve_a = pool.reserve_a.saturating_add(token_a_amount);
         pool.reserve_b = pool.reserve_b.saturating_add(token_b_amount);
-        pool.total_lp_tokens = pool.total_lp_tokens.saturating_add(lp_tokens);
+        pool.total_lp_tokens = pool.total_lp_tokens.saturating_add(lp_tokens + locked);
         
         // Update pool APY based on new liquidity
-        pool.current_apy = Self::calculate_dynamic_apy(&pool)?;
+        pool.current_apy = Self::calculate_dynamic_apy(&env, &pool)?;
         
         // Store updated pool
         env.storage().persistent().set(&DataKey::Pool(pool_id.clone()), &pool);
         
-        // Create or update position
-        Self::update_liquidity_position(&env, &provider, "XLM_USDC", lp_tokens, token_a_amount, token_b_amount)?;
+        // Mint LP shares to the provider; the first deposit also locks shares with this contract
+        let lp_token = LpTokenClient::new(&env, &pool.lp_token);
+        if locked > 0 {
+            lp_token.mint(&env.current_contract_address(), &locked);
+        }
+        lp_token.mint(&provider, &lp_tokens);
+        
+        // Create or update position; the locked shares' part of a first deposit is not the provider's basis
+        let basis_a = Self::mul_div(token_a_amount, lp_tokens, lp_tokens + locked)?;
+        let basis_b = Self::mul_div(token_b_amount, lp_tokens, lp_tokens + locked)?;
+        Self::update_liquidity_position(&env, &provider, &pool, lp_tokens, basis_a, basis_b)?;
         
         // Update global stats
-        Self::update_global_stats(&env, token_a_amount + token_b_amount, true)?;
+        Self::update_global_stats(&env, &pool);
         
-        Ok(lp_tokens)
+        Ok(LiquidityDeposit {
+            amount_a: token_a_amount,
+            amount_b: token_b_amount,
+            lp_tokens,
+        })
     }
 
     /// Remove liquidity from pool
@@ -250,311 +607,1739 @@ impl UltimateLiquidityContract {
         provider: Address,
         pool_id: String,
         lp_tokens: i128,
+        min_a: i128,
+        min_b: i128,
+        deadline: u64,
     ) -> Result<(i128, i128), LiquidityError> {
         provider.require_auth();
         
+        if env.ledger().timestamp() > deadline {
+            return Err(LiquidityError::DeadlineExpired);
+        }
+        
         // Get pool and position
         let mut pool = env.storage().persistent()
             .get::<DataKey, LiquidityPool>(&DataKey::Pool(pool_id.clone()))
             .ok_or(LiquidityError::PoolNotFound)?;
         
-        let mut position = env.storage().persistent()
-            .get::<DataKey, LiquidityPosition>(&DataKey::Position(provider.clone(), pool_id.clone()))
-            .ok_or(LiquidityError::InsufficientLiquidity)?;
-        
-        // Validate LP token amount
-        if lp_tokens > position.lp_tokens {
+        // Validate LP token amount against the provider's share balance
+        let lp_token = TokenClient::new(&env, &pool.lp_token);
+        if lp_tokens <= 0 || lp_tokens > lp_token.balance(&provider) {
             return Err(LiquidityError::InsufficientLiquidity);
         }
         
         // Calculate withdrawal amounts
         let (token_a_amount, token_b_amount) = Self::calculate_withdrawal_amounts(&pool, lp_tokens)?;
+        if token_a_amount < min_a || token_b_amount < min_b {
+            return Err(LiquidityError::SlippageExceeded);
+        }
         
-        // Calculate impermanent loss
-        let impermanent_loss = Self::calculate_impermanent_loss(&position, &pool, token_a_amount, token_b_amount)?;
+        // Staked shares are held by this contract, so only unstaked shares can be redeemed
+        let position_key = DataKey::Position(provider.clone(), pool_id.clone());
+        let position = env.storage().persistent().get::<DataKey, LiquidityPosition>(&position_key);
+        
+        // Burn the redeemed shares
+        lp_token.burn(&provider, &lp_tokens);
         
         // Update pool reserves
         pool.reserve_a = pool.reserve_a.saturating_sub(token_a_amount);
         pool.reserve_b = pool.reserve_b.saturating_sub(token_b_amount);
         pool.total_lp_tokens = pool.total_lp_tokens.saturating_sub(lp_tokens);
+        pool.current_apy = Self::calculate_dynamic_apy(&env, &pool)?;
+        env.storage().persistent().set(&DataKey::Pool(pool_id.clone()), &pool);
         
-        // Update position
-        position.lp_tokens = position.lp_tokens.saturating_sub(lp_tokens);
-        position.impermanent_loss = position.impermanent_loss.saturating_add(impermanent_loss);
+        // Update the provider's deposit record; shares received by transfer have none
+        if let Some(mut position) = position {
+            let shares = lp_tokens.min(position.lp_tokens);
+            if shares > 0 {
+                // Attribute the withdrawn amounts to this position's shares
+                let share_a = Self::mul_div(token_a_amount, shares, lp_tokens)?;
+                let share_b = Self::mul_div(token_b_amount, shares, lp_tokens)?;
+                let (fee_a, fee_b) = Self::calculate_pending_fees(&pool, &position, shares)?;
+                let hold_a = Self::mul_div(position.initial_token_a, shares, position.lp_tokens)?;
+                let hold_b = Self::mul_div(position.initial_token_b, shares, position.lp_tokens)?;
+                
+                // Withdrawals never fail for lack of a price; IL is just left unrecorded
+                let impermanent_loss = Self::calculate_impermanent_loss(
+                    &env, &pool, hold_a, hold_b, share_a - fee_a, share_b - fee_b,
+                ).unwrap_or(0);
+                
+                Self::track_provider(&env, &provider, position.lp_tokens, position.lp_tokens - shares);
+                position.lp_tokens -= shares;
+                position.initial_token_a -= hold_a;
+                position.initial_token_b -= hold_b;
+                position.fees_earned_a = position.fees_earned_a.saturating_add(fee_a);
+                position.fees_earned_b = position.fees_earned_b.saturating_add(fee_b);
+                position.impermanent_loss = position.impermanent_loss.saturating_add(impermanent_loss);
+                
+                // Compensate from the protection fund according to position age
+                let compensated = Self::pay_il_compensation(&env, &pool, &position, impermanent_loss)?;
+                position.il_compensated = position.il_compensated.saturating_add(compensated);
+            }
+            env.storage().persistent().set(&position_key, &position);
+        }
         
-        // Store updates
-        env.storage().persistent().set(&DataKey::Pool(pool_id.clone()), &pool);
-        env.storage().persistent().set(&DataKey::Position(provider, pool_id), &position);
+        // Pay out the withdrawn tokens
+        let contract = env.current_contract_address();
+        TokenClient::new(&env, &pool.token_a).transfer(&contract, &provider, &token_a_amount);
+        TokenClient::new(&env, &pool.token_b).transfer(&contract, &provider, &token_b_amount);
+        Self::adjust_token_reserves(&env, &pool.token_a, -token_a_amount);
+        Self::adjust_token_reserves(&env, &pool.token_b, -token_b_amount);
         
         // Update global stats
-        Self::update_global_stats(&env, token_a_amount + token_b_amount, false)?;
+        Self::update_global_stats(&env, &pool);
         
         Ok((token_a_amount, token_b_amount))
     }
 
-    /// Claim yield farming rewards
+    /// Claim liquidity mining rewards
     pub fn claim_rewards(
         env: Env,
         provider: Address,
         pool_id: String,
-    ) -> Result<YieldFarmRewards, LiquidityError> {
+    ) -> Result<RewardPayout, LiquidityError> {
         provider.require_auth();
         
         // Get position
         let mut position = env.storage().persistent()
             .get::<DataKey, LiquidityPosition>(&DataKey::Position(provider.clone(), pool_id.clone()))
             .ok_or(LiquidityError::InsufficientLiquidity)?;
+        let mut pool = Self::get_pool(env.clone(), pool_id.clone())?;
         
-        // Calculate rewards
-        let rewards = Self::calculate_yield_rewards(&env, &position, "XLM_USDC")?;
+        // Settle rewards accrued up to now
+        let rewards = Self::sync_rewards(&env, &pool, &mut position, 0)?;
+        let reward_token = rewards.reward_token.ok_or(LiquidityError::RewardClaimFailed)?;
+        let amount = position.unclaimed_rewards;
         
         // Update position
-        position.accumulated_rewards = position.accumulated_rewards.saturating_add(rewards.swave_rewards);
+        position.unclaimed_rewards = 0;
+        position.accumulated_rewards = position.accumulated_rewards.saturating_add(amount);
         position.last_reward_claim = env.ledger().timestamp();
         
         // Store updated position
-        env.storage().persistent().set(&DataKey::Position(provider, pool_id), &position);
+        env.storage().persistent().set(&DataKey::Position(provider.clone(), pool_id.clone()), &position);
         
-        Ok(rewards)
-    }
-
-    /// Calculate LP tokens to issue
-    fn calculate_lp_tokens(
-        pool: &LiquidityPool,
-        token_a_amount: i128,
-        token_b_amount: i128,
-    ) -> Result<i128, LiquidityError> {
-        if pool.total_lp_tokens == 0 {
-            // First liquidity provision
-            Ok((token_a_amount * token_b_amount / 1000000) as i128) // Simplified geometric mean
-        } else {
-            // Proportional to existing pool
-            let lp_a = (token_a_amount * pool.total_lp_tokens) / pool.reserve_a;
-            let lp_b = (token_b_amount * pool.total_lp_tokens) / pool.reserve_b;
-            Ok(lp_a.min(lp_b))
+        if amount > 0 {
+            pool.total_rewards_distributed = pool.total_rewards_distributed.saturating_add(amount);
+            env.storage().persistent().set(&DataKey::Pool(pool_id), &pool);
+            Self::record_rewards_distributed(&env, &reward_token, amount);
+            
+            TokenClient::new(&env, &reward_token).transfer(&env.current_contract_address(), &provider, &amount);
+            Self::adjust_token_reserves(&env, &reward_token, -amount);
         }
+        
+        Ok(RewardPayout { reward_token, amount })
     }
 
-    /// Calculate withdrawal amounts
-    fn calculate_withdrawal_amounts(
-        pool: &LiquidityPool,
-        lp_tokens: i128,
-    ) -> Result<(i128, i128), LiquidityError> {
-        if pool.total_lp_tokens == 0 {
-            return Err(LiquidityError::InsufficientLiquidity);
+    /// Set a pool's reward token and per-second emission rate (admin only)
+    pub fn set_reward_emission(
+        env: Env,
+        caller: Address,
+        pool_id: String,
+        reward_token: Address,
+        emission_rate: i128,
+    ) -> Result<(), LiquidityError> {
+        Self::require_admin(&env, &caller)?;
+        
+        if emission_rate < 0 {
+            return Err(LiquidityError::InvalidAmount);
         }
+        Self::get_pool(env.clone(), pool_id.clone())?;
         
-        let token_a = (lp_tokens * pool.reserve_a) / pool.total_lp_tokens;
-        let token_b = (lp_tokens * pool.reserve_b) / pool.total_lp_tokens;
+        // Accrue at the old rate before switching
+        let mut rewards = Self::update_pool_rewards(&env, &pool_id);
+        if let Some(current) = &rewards.reward_token {
+            if *current != reward_token && rewards.reward_reserve > 0 {
+                return Err(LiquidityError::DistributionFailed);
+            }
+        }
         
-        Ok((token_a, token_b))
+        rewards.reward_token = Some(reward_token);
+        rewards.emission_rate = emission_rate;
+        env.storage().persistent().set(&DataKey::PoolRewards(pool_id), &rewards);
+        Ok(())
     }
 
-    /// Calculate dynamic APY based on pool metrics
-    fn calculate_dynamic_apy(pool: &LiquidityPool) -> Result<u32, LiquidityError> {
-        let base_apy = BASE_APY;
+    /// Deposit reward tokens to be emitted to a pool's LPs
+    pub fn fund_rewards(
+        env: Env,
+        funder: Address,
+        pool_id: String,
+        amount: i128,
+    ) -> Result<(), LiquidityError> {
+        funder.require_auth();
         
-        // Calculate utilization-based adjustment
-        let tvl = pool.reserve_a + pool.reserve_b;
-        let utilization_bonus = if tvl > 1_000_000_000_000 { // > 100K XLM
-            200 // +2% bonus for high TVL
-        } else if tvl > 100_000_000_000 { // > 10K XLM
-            100 // +1% bonus
-        } else {
-            0
-        };
+        if amount <= 0 {
+            return Err(LiquidityError::InvalidAmount);
+        }
         
-        // Calculate time-based bonus
-        let current_time = 1234567890u64; // Would use env.ledger().timestamp() in real implementation
-        let pool_age_days = (current_time - pool.created_at) / 86400;
-        let age_bonus = if pool_age_days > 30 {
-            50 // +0.5% for mature pools
-        } else {
-            0
-        };
+        let mut rewards = Self::update_pool_rewards(&env, &pool_id);
+        let reward_token = rewards.reward_token.clone().ok_or(LiquidityError::DistributionFailed)?;
+        
+        TokenClient::new(&env, &reward_token).transfer(&funder, &env.current_contract_address(), &amount);
+        Self::adjust_token_reserves(&env, &reward_token, amount);
         
-        let total_apy = base_apy + utilization_bonus + age_bonus;
-        Ok(total_apy.min(MAX_APY))
+        rewards.reward_reserve = rewards.reward_reserve.saturating_add(amount);
+        env.storage().persistent().set(&DataKey::PoolRewards(pool_id), &rewards);
+        Ok(())
     }
 
-    /// Calculate yield farming rewards
-    fn calculate_yield_rewards(
-        env: &Env,
-        position: &LiquidityPosition,
-        pool_id: &str,
-    ) -> Result<YieldFarmRewards, LiquidityError> {
-        let current_time = env.ledger().timestamp();
-        let time_elapsed = current_time - position.last_reward_claim;
-        
-        // Get pool for APY calculation
-        let pool = env.storage().persistent()
-            .get::<DataKey, LiquidityPool>(&DataKey::Pool(String::from_str(env, pool_id)))
-            .ok_or(LiquidityError::PoolNotFound)?;
-        
-        // Calculate base rewards
-        let days_elapsed = time_elapsed / 86400;
-        let annual_reward_rate = pool.current_apy as i128;
-        let daily_reward_rate = annual_reward_rate / 365;
-        
-        // Calculate rewards based on LP tokens held
-        let base_rewards = (position.lp_tokens * daily_reward_rate * days_elapsed as i128) / 10000;
-        
-        // Distribute rewards across different types
-        let swave_rewards = (base_rewards * 60) / 100; // 60% SWAVE
-        let lp_rewards = (base_rewards * 30) / 100;     // 30% LP tokens
-        let fee_rewards = (base_rewards * 10) / 100;    // 10% fees
-        
-        Ok(YieldFarmRewards {
-            swave_rewards,
-            lp_rewards,
-            fee_rewards,
-            total_value_usd: base_rewards,
-            effective_apy: pool.current_apy,
-            period_start: position.last_reward_claim,
-            period_end: current_time,
-        })
+    /// Get a pool's liquidity mining state, accrued to now
+    pub fn get_pool_rewards(env: Env, pool_id: String) -> PoolRewards {
+        Self::accrue_pool_rewards(&env, Self::load_pool_rewards(&env, &pool_id))
     }
 
-    /// Calculate impermanent loss
-    fn calculate_impermanent_loss(
-        position: &LiquidityPosition,
-        _pool: &LiquidityPool,
-        withdrawn_a: i128,
-        withdrawn_b: i128,
-    ) -> Result<i128, LiquidityError> {
-        // Simplified impermanent loss calculation
-        let initial_value = position.initial_token_a + position.initial_token_b;
-        let withdrawn_value = withdrawn_a + withdrawn_b;
+    /// Allow a campaign reward token with a minimum funding amount, or disallow it with a
+    /// minimum of zero (admin only)
+    pub fn set_campaign_token(
+        env: Env,
+        caller: Address,
+        reward_token: Address,
+        min_amount: i128,
+    ) -> Result<(), LiquidityError> {
+        Self::require_admin(&env, &caller)?;
         
-        if withdrawn_value < initial_value {
-            Ok(initial_value - withdrawn_value)
+        let key = DataKey::CampaignToken(reward_token);
+        if min_amount <= 0 {
+            env.storage().persistent().remove(&key);
         } else {
-            Ok(0)
+            env.storage().persistent().set(&key, &min_amount);
         }
+        Ok(())
     }
 
-    /// Get or create pool
-    fn get_or_create_pool(
-        env: &Env,
-        pool_id: &str,
-        _initial_a: i128,
-        _initial_b: i128,
-    ) -> Result<LiquidityPool, LiquidityError> {
-        if let Some(pool) = env.storage().persistent().get::<DataKey, LiquidityPool>(&DataKey::Pool(String::from_str(env, pool_id))) {
-            Ok(pool)
-        } else {
-            // Create new pool
-            let pool = LiquidityPool {
-                pool_id: String::from_str(env, pool_id),
-                token_a: env.current_contract_address(),
-                token_b: env.current_contract_address(),
-                reserve_a: 0,
-                reserve_b: 0,
-                total_lp_tokens: 0,
-                current_apy: BASE_APY,
-                created_at: env.ledger().timestamp(),
-                last_reward_distribution: env.ledger().timestamp(),
-                total_rewards_distributed: 0,
-                active: true,
-            };
-            
-            Ok(pool)
+    /// Minimum funding of a campaign paying `reward_token`, if the token is allowed
+    pub fn get_campaign_token(env: Env, reward_token: Address) -> Option<i128> {
+        env.storage().persistent().get(&DataKey::CampaignToken(reward_token))
+    }
+
+    /// Fund a reward campaign that emits `amount` of `reward_token` to a pool's LPs between `start` and `end`.
+    /// The reward token must be whitelisted and the campaign funded with at least its minimum.
+    pub fn create_campaign(
+        env: Env,
+        creator: Address,
+        pool_id: String,
+        reward_token: Address,
+        amount: i128,
+        start: u64,
+        end: u64,
+    ) -> Result<u32, LiquidityError> {
+        creator.require_auth();
+        
+        let now = env.ledger().timestamp();
+        if amount <= 0 || start < now || end <= start || end - start > MAX_CAMPAIGN_DURATION {
+            return Err(LiquidityError::InvalidAmount);
+        }
+        Self::get_pool(env.clone(), pool_id.clone())?;
+        
+        // Every staker settles every campaign, so dust campaigns must not crowd the list
+        let min_amount = Self::get_campaign_token(env.clone(), reward_token.clone())
+            .ok_or(LiquidityError::RewardTokenNotAllowed)?;
+        if amount < min_amount {
+            return Err(LiquidityError::InvalidAmount);
+        }
+        
+        // Drop campaigns past their claim period and cap the unfinished ones
+        let mut campaign_ids = Vec::new(&env);
+        let mut active = 0u32;
+        for id in Self::get_campaign_ids(&env, &pool_id).iter() {
+            let campaign = Self::load_campaign(&env, id)?;
+            if campaign.end.saturating_add(CAMPAIGN_CLAIM_PERIOD) < now {
+                Self::close_campaign(&env, campaign);
+                continue;
+            }
+            if campaign.end > now {
+                active += 1;
+            }
+            campaign_ids.push_back(id);
+        }
+        if active >= MAX_ACTIVE_CAMPAIGNS || campaign_ids.len() >= MAX_POOL_CAMPAIGNS {
+            return Err(LiquidityError::PoolCapExceeded);
         }
+        
+        TokenClient::new(&env, &reward_token).transfer(&creator, &env.current_contract_address(), &amount);
+        Self::adjust_token_reserves(&env, &reward_token, amount);
+        
+        let id: u32 = env.storage().instance().get(&DataKey::NextCampaignId).unwrap_or(0);
+        let campaign = RewardCampaign {
+            id,
+            pool_id: pool_id.clone(),
+            creator,
+            reward_token,
+            total_amount: amount,
+            start,
+            end,
+            acc_reward_per_share: 0,
+            last_update: start,
+            emitted: 0,
+            claimed: 0,
+        };
+        campaign_ids.push_back(id);
+        
+        env.storage().instance().set(&DataKey::NextCampaignId, &(id + 1));
+        env.storage().persistent().set(&DataKey::Campaign(id), &campaign);
+        env.storage().persistent().set(&DataKey::PoolCampaigns(pool_id), &campaign_ids);
+        Ok(id)
     }
 
-    /// Update liquidity position
-    fn update_liquidity_position(
-        env: &Env,
-        provider: &Address,
-        pool_id: &str,
-        lp_tokens: i128,
-        token_a: i128,
-        token_b: i128,
-    ) -> Result<(), LiquidityError> {
-        let position_key = DataKey::Position(provider.clone(), String::from_str(env, pool_id));
+    /// Return a finished campaign's rewards that were never emitted to its creator
+    pub fn withdraw_unemitted(
+        env: Env,
+        creator: Address,
+        campaign_id: u32,
+    ) -> Result<i128, LiquidityError> {
+        creator.require_auth();
         
-        if let Some(mut position) = env.storage().persistent().get::<DataKey, LiquidityPosition>(&position_key) {
-            // Update existing position
-            position.lp_tokens = position.lp_tokens.saturating_add(lp_tokens);
-            env.storage().persistent().set(&position_key, &position);
-        } else {
-            // Create new position
-            let position = LiquidityPosition {
-                provider: provider.clone(),
-                pool_id: String::from_str(env, pool_id),
-                lp_tokens,
-                deposited_at: env.ledger().timestamp(),
-                initial_token_a: token_a,
-                initial_token_b: token_b,
-                accumulated_rewards: 0,
-                last_reward_claim: env.ledger().timestamp(),
-                impermanent_loss: 0,
-            };
-            
-            env.storage().persistent().set(&position_key, &position);
+        let campaign = Self::load_campaign(&env, campaign_id)?;
+        if campaign.creator != creator {
+            return Err(LiquidityError::UnauthorizedAccess);
+        }
+        if env.ledger().timestamp() < campaign.end {
+            return Err(LiquidityError::DistributionFailed);
         }
         
-        Ok(())
+        let rewards = Self::load_pool_rewards(&env, &campaign.pool_id);
+        let mut campaign = Self::accrue_campaign(&env, campaign, rewards.total_staked);
+        let amount = campaign.total_amount - campaign.emitted;
+        campaign.total_amount = campaign.emitted;
+        env.storage().persistent().set(&DataKey::Campaign(campaign_id), &campaign);
+        
+        if amount > 0 {
+            TokenClient::new(&env, &campaign.reward_token).transfer(&env.current_contract_address(), &creator, &amount);
+            Self::adjust_token_reserves(&env, &campaign.reward_token, -amount);
+        }
+        Ok(amount)
     }
 
-    /// Update global statistics
-    fn update_global_stats(
-        env: &Env,
-        amount: i128,
-        is_addition: bool,
-    ) -> Result<(), LiquidityError> {
-        let mut stats = env.storage().instance()
-            .get::<DataKey, GlobalLiquidityStats>(&DataKey::GlobalStats)
-            .unwrap_or_else(|| GlobalLiquidityStats {
-                total_tvl_usd: 0,
-                total_pools: 0,
-                active_providers: 0,
-                total_rewards_distributed: 0,
-                average_apy: BASE_APY,
-            });
+    /// Claim pool emissions and every campaign of a pool in one call
+    pub fn claim_all(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+    ) -> Result<Vec<RewardPayout>, LiquidityError> {
+        provider.require_auth();
         
-        if is_addition {
-            stats.total_tvl_usd = stats.total_tvl_usd.saturating_add(amount);
-            stats.active_providers = stats.active_providers.saturating_add(1);
-        } else {
-            stats.total_tvl_usd = stats.total_tvl_usd.saturating_sub(amount);
+        let position_key = DataKey::Position(provider.clone(), pool_id.clone());
+        let mut position = env.storage().persistent()
+            .get::<DataKey, LiquidityPosition>(&position_key)
+            .ok_or(LiquidityError::InsufficientLiquidity)?;
+        let mut pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        
+        // Settles pool emissions and all campaigns up to now
+        let rewards = Self::sync_rewards(&env, &pool, &mut position, 0)?;
+        let contract = env.current_contract_address();
+        let mut payouts = Vec::new(&env);
+        
+        if let Some(reward_token) = rewards.reward_token {
+            let amount = position.unclaimed_rewards;
+            if amount > 0 {
+                position.unclaimed_rewards = 0;
+                position.accumulated_rewards = position.accumulated_rewards.saturating_add(amount);
+                pool.total_rewards_distributed = pool.total_rewards_distributed.saturating_add(amount);
+                Self::record_rewards_distributed(&env, &reward_token, amount);
+                TokenClient::new(&env, &reward_token).transfer(&contract, &provider, &amount);
+                Self::adjust_token_reserves(&env, &reward_token, -amount);
+                payouts.push_back(RewardPayout { reward_token, amount });
+            }
         }
         
-        env.storage().instance().set(&DataKey::GlobalStats, &stats);
-        Ok(())
+        for id in Self::get_campaign_ids(&env, &pool_id).iter() {
+            let Ok(mut campaign) = Self::load_campaign(&env, id) else {
+                continue;
+            };
+            let key = DataKey::CampaignPosition(id, provider.clone());
+            let mut standing = Self::load_campaign_position(&env, id, &provider);
+            let amount = standing.unclaimed;
+            if amount <= 0 {
+                continue;
+            }
+            
+            let first_claim = standing.claimed == 0;
+            standing.unclaimed = 0;
+            standing.claimed += amount;
+            env.storage().persistent().set(&key, &standing);
+            campaign.claimed += amount;
+            env.storage().persistent().set(&DataKey::Campaign(id), &campaign);
+            
+            Self::record_distribution(&env, &pool, campaign.start, amount, first_claim);
+            pool.total_rewards_distributed = pool.total_rewards_distributed.saturating_add(amount);
+            Self::record_rewards_distributed(&env, &campaign.reward_token, amount);
+            TokenClient::new(&env, &campaign.reward_token).transfer(&contract, &provider, &amount);
+            Self::adjust_token_reserves(&env, &campaign.reward_token, -amount);
+            payouts.push_back(RewardPayout { reward_token: campaign.reward_token, amount });
+        }
+        
+        position.last_reward_claim = env.ledger().timestamp();
+        env.storage().persistent().set(&position_key, &position);
+        env.storage().persistent().set(&DataKey::Pool(pool_id), &pool);
+        
+        Ok(payouts)
     }
 
-    /// Get pool information
-    pub fn get_pool(env: Env, pool_id: String) -> Result<LiquidityPool, LiquidityError> {
-        env.storage().persistent()
-            .get::<DataKey, LiquidityPool>(&DataKey::Pool(pool_id))
-            .ok_or(LiquidityError::PoolNotFound)
+    /// Get a reward campaign, accrued to now
+    pub fn get_campaign(env: Env, campaign_id: u32) -> Result<RewardCampaign, LiquidityError> {
+        let campaign = Self::load_campaign(&env, campaign_id)?;
+        let rewards = Self::load_pool_rewards(&env, &campaign.pool_id);
+        Ok(Self::accrue_campaign(&env, campaign, rewards.total_staked))
     }
 
-    /// Get user position
-    pub fn get_position(
+    /// Campaigns still claimable on a pool
+    pub fn get_pool_campaigns(env: Env, pool_id: String) -> Vec<u32> {
+        Self::get_campaign_ids(&env, &pool_id)
+    }
+
+    /// Campaign rewards distributed on a pool for campaigns starting at `timestamp`
+    pub fn get_distribution(
         env: Env,
-        provider: Address,
         pool_id: String,
-    ) -> Result<LiquidityPosition, LiquidityError> {
+        timestamp: u64,
+    ) -> Result<RewardDistribution, LiquidityError> {
         env.storage().persistent()
-            .get::<DataKey, LiquidityPosition>(&DataKey::Position(provider, pool_id))
-            .ok_or(LiquidityError::InsufficientLiquidity)
+            .get(&DataKey::Distribution(pool_id, timestamp))
+            .ok_or(LiquidityError::DistributionFailed)
     }
 
-    /// Get global statistics
-    pub fn get_global_stats(env: Env) -> Result<GlobalLiquidityStats, LiquidityError> {
-        // Return default stats if not initialized yet
-        let default_stats = GlobalLiquidityStats {
-            total_tvl_usd: 0,
-            total_pools: 0,
-            active_providers: 0,
-            total_rewards_distributed: 0,
-            average_apy: BASE_APY,
-        };
+    /// Rewards a provider could claim now
+    pub fn get_pending_rewards(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+    ) -> Result<i128, LiquidityError> {
+        let position = Self::get_position(env.clone(), provider, pool_id.clone())?;
+        let rewards = Self::get_pool_rewards(env, pool_id);
         
-        Ok(env.storage().instance()
-            .get::<DataKey, GlobalLiquidityStats>(&DataKey::GlobalStats)
-            .unwrap_or(default_stats))
+        let accrued = Self::mul_div(position.reward_weight, rewards.acc_reward_per_share, REWARD_PRECISION)?;
+        Ok(position.unclaimed_rewards + accrued - position.reward_debt)
     }
-} 
\ No newline at end of file
+
+    /// Stake LP shares so they earn liquidity mining rewards. Staked shares are held by
+    /// this contract until unstaked; returns the provider's total stake.
+    pub fn stake(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+        lp_tokens: i128,
+    ) -> Result<i128, LiquidityError> {
+        provider.require_auth();
+        
+        if lp_tokens <= 0 {
+            return Err(LiquidityError::InvalidAmount);
+        }
+        
+        let pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        let position_key = DataKey::Position(provider.clone(), pool_id);
+        let mut position = Self::load_position(&env, &provider, &pool);
+        
+        let lp_token = TokenClient::new(&env, &pool.lp_token);
+        if lp_tokens > lp_token.balance(&provider) {
+            return Err(LiquidityError::InsufficientLiquidity);
+        }
+        lp_token.transfer(&provider, &env.current_contract_address(), &lp_tokens);
+        
+        Self::sync_rewards(&env, &pool, &mut position, lp_tokens)?;
+        env.storage().persistent().set(&position_key, &position);
+        
+        Ok(position.reward_stake)
+    }
+
+    /// Return staked LP shares that are not locked; returns the provider's remaining stake
+    pub fn unstake(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+        lp_tokens: i128,
+    ) -> Result<i128, LiquidityError> {
+        provider.require_auth();
+        
+        if lp_tokens <= 0 {
+            return Err(LiquidityError::InvalidAmount);
+        }
+        
+        let pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        let position_key = DataKey::Position(provider.clone(), pool_id);
+        let mut position = env.storage().persistent()
+            .get::<DataKey, LiquidityPosition>(&position_key)
+            .ok_or(LiquidityError::InsufficientLiquidity)?;
+        
+        if lp_tokens > position.reward_stake - position.locked_shares {
+            return Err(LiquidityError::InsufficientLiquidity);
+        }
+        
+        Self::sync_rewards(&env, &pool, &mut position, -lp_tokens)?;
+        env.storage().persistent().set(&position_key, &position);
+        TokenClient::new(&env, &pool.lp_token).transfer(&env.current_contract_address(), &provider, &lp_tokens);
+        
+        Ok(position.reward_stake)
+    }
+
+    /// LP shares a provider has staked in a pool, locked shares included
+    pub fn get_staked_shares(env: Env, provider: Address, pool_id: String) -> i128 {
+        env.storage().persistent()
+            .get::<DataKey, LiquidityPosition>(&DataKey::Position(provider, pool_id))
+            .map(|position| position.reward_stake)
+            .unwrap_or(0)
+    }
+
+    /// Lock LP shares for 1, 3, 6 or 12 months to boost their mining rewards. Unlocked staked
+    /// shares are locked first, the rest are taken from the provider's balance.
+    /// Locking again while a lock is active adds to it and restarts it; the tier may not get shorter.
+    pub fn lock_position(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+        lp_tokens: i128,
+        months: u32,
+    ) -> Result<u64, LiquidityError> {
+        provider.require_auth();
+        
+        let (duration, multiplier) = Self::lock_tier(months)?;
+        if lp_tokens <= 0 {
+            return Err(LiquidityError::InvalidAmount);
+        }
+        
+        let pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        let position_key = DataKey::Position(provider.clone(), pool_id);
+        let mut position = Self::load_position(&env, &provider, &pool);
+        
+        let now = env.ledger().timestamp();
+        if position.lock_until > now && duration < position.lock_duration {
+            return Err(LiquidityError::InvalidLockPeriod);
+        }
+        
+        // Lock staked shares first, then take the rest into custody until the lock is released
+        let from_wallet = (lp_tokens - (position.reward_stake - position.locked_shares)).max(0);
+        if from_wallet > 0 {
+            let lp_token = TokenClient::new(&env, &pool.lp_token);
+            if from_wallet > lp_token.balance(&provider) {
+                return Err(LiquidityError::InsufficientLiquidity);
+            }
+            lp_token.transfer(&provider, &env.current_contract_address(), &from_wallet);
+        }
+        
+        position.locked_shares += lp_tokens;
+        position.lock_until = now + duration;
+        position.lock_duration = duration;
+        position.lock_multiplier = multiplier;
+        
+        Self::sync_rewards(&env, &pool, &mut position, from_wallet)?;
+        env.storage().persistent().set(&position_key, &position);
+        
+        Ok(position.lock_until)
+    }
+
+    /// Release locked LP shares. Before the lock ends, a penalty of up to
+    /// MAX_EARLY_EXIT_PENALTY, decaying linearly over the lock, is burned; the
+    /// liquidity behind it stays in the pool for the remaining LPs.
+    pub fn unlock_position(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+    ) -> Result<i128, LiquidityError> {
+        provider.require_auth();
+        
+        let mut pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        let position_key = DataKey::Position(provider.clone(), pool_id.clone());
+        let mut position = env.storage().persistent()
+            .get::<DataKey, LiquidityPosition>(&position_key)
+            .ok_or(LiquidityError::InsufficientLiquidity)?;
+        
+        let locked = position.locked_shares;
+        if locked <= 0 {
+            return Err(LiquidityError::InsufficientLiquidity);
+        }
+        
+        let now = env.ledger().timestamp();
+        let penalty = if now < position.lock_until {
+            let max_penalty = Self::mul_div(locked, MAX_EARLY_EXIT_PENALTY, BASIS_POINTS)?;
+            Self::mul_div(max_penalty, (position.lock_until - now) as i128, position.lock_duration as i128)?
+        } else {
+            0
+        };
+        let returned = locked - penalty;
+        
+        // Return the shares unstaked; rewards up to now are settled below at the boosted weight
+        let contract = env.current_contract_address();
+        let lp_token = TokenClient::new(&env, &pool.lp_token);
+        lp_token.transfer(&contract, &provider, &returned);
+        if penalty > 0 {
+            lp_token.burn(&contract, &penalty);
+            pool.total_lp_tokens = pool.total_lp_tokens.saturating_sub(penalty);
+            env.storage().persistent().set(&DataKey::Pool(pool_id), &pool);
+            
+            // The burned shares leave the deposit record without counting as impermanent loss
+            let shares = penalty.min(position.lp_tokens);
+            if shares > 0 {
+                position.initial_token_a -= Self::mul_div(position.initial_token_a, shares, position.lp_tokens)?;
+                position.initial_token_b -= Self::mul_div(position.initial_token_b, shares, position.lp_tokens)?;
+                Self::track_provider(&env, &provider, position.lp_tokens, position.lp_tokens - shares);
+                position.lp_tokens -= shares;
+            }
+        }
+        
+        position.locked_shares = 0;
+        position.lock_until = 0;
+        position.lock_duration = 0;
+        position.lock_multiplier = BASIS_POINTS as u32;
+        
+        Self::sync_rewards(&env, &pool, &mut position, -locked)?;
+        env.storage().persistent().set(&position_key, &position);
+        
+        Ok(returned)
+    }
+
+    /// Drop the boost of an expired lock so it stops earning boosted rewards; callable by anyone
+    pub fn kick_lock(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+    ) -> Result<(), LiquidityError> {
+        let pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        let position_key = DataKey::Position(provider, pool_id);
+        let mut position = env.storage().persistent()
+            .get::<DataKey, LiquidityPosition>(&position_key)
+            .ok_or(LiquidityError::InsufficientLiquidity)?;
+        
+        if position.locked_shares > 0 && env.ledger().timestamp() < position.lock_until {
+            return Err(LiquidityError::PositionLocked);
+        }
+        
+        Self::sync_rewards(&env, &pool, &mut position, 0)?;
+        env.storage().persistent().set(&position_key, &position);
+        
+        Ok(())
+    }
+
+    /// Set the swap contract that settles trades against pool reserves (admin only)
+    pub fn set_swap_contract(
+        env: Env,
+        caller: Address,
+        swap_contract: Address,
+    ) -> Result<(), LiquidityError> {
+        caller.require_auth();
+        
+        let admin: Address = env.storage().instance()
+            .get(&DataKey::Admin)
+            .ok_or(LiquidityError::UnauthorizedAccess)?;
+        
+        if caller != admin {
+            return Err(LiquidityError::UnauthorizedAccess);
+        }
+        
+        env.storage().instance().set(&DataKey::SwapContract, &swap_contract);
+        Ok(())
+    }
+
+    /// Set the LP token wasm deployed for new pools (admin only)
+    pub fn set_lp_token_wasm(
+        env: Env,
+        caller: Address,
+        wasm_hash: BytesN<32>,
+    ) -> Result<(), LiquidityError> {
+        caller.require_auth();
+        
+        let admin: Address = env.storage().instance()
+            .get(&DataKey::Admin)
+            .ok_or(LiquidityError::UnauthorizedAccess)?;
+        
+        if caller != admin {
+            return Err(LiquidityError::UnauthorizedAccess);
+        }
+        
+        env.storage().instance().set(&DataKey::LpTokenWasm, &wasm_hash);
+        Ok(())
+    }
+
+    /// Set the oracle used to value positions (admin only)
+    pub fn set_oracle(
+        env: Env,
+        caller: Address,
+        oracle: Address,
+    ) -> Result<(), LiquidityError> {
+        Self::require_admin(&env, &caller)?;
+        
+        env.storage().instance().set(&DataKey::Oracle, &oracle);
+        Ok(())
+    }
+
+    /// Configure impermanent loss protection (admin only)
+    pub fn set_protection_config(
+        env: Env,
+        caller: Address,
+        config: ProtectionConfig,
+    ) -> Result<(), LiquidityError> {
+        Self::require_admin(&env, &caller)?;
+        
+        if config.fee_share_bps as i128 > BASIS_POINTS
+            || config.max_coverage_bps as i128 > BASIS_POINTS
+            || config.vesting_period == 0
+            || config.max_payout_usd < 0
+        {
+            return Err(LiquidityError::InvalidAmount);
+        }
+        
+        env.storage().instance().set(&DataKey::ProtectionConfig, &config);
+        Ok(())
+    }
+
+    /// Get impermanent loss protection settings (protection is off until configured)
+    pub fn get_protection_config(env: Env) -> ProtectionConfig {
+        env.storage().instance()
+            .get(&DataKey::ProtectionConfig)
+            .unwrap_or(ProtectionConfig {
+                fee_share_bps: 0,
+                max_coverage_bps: 0,
+                vesting_period: IL_VESTING_PERIOD,
+                max_payout_usd: 0,
+            })
+    }
+
+    /// Top up a pool's protection fund with either pool token
+    pub fn fund_protection(
+        env: Env,
+        funder: Address,
+        pool_id: String,
+        token: Address,
+        amount: i128,
+    ) -> Result<(), LiquidityError> {
+        funder.require_auth();
+        
+        if amount <= 0 {
+            return Err(LiquidityError::InvalidAmount);
+        }
+        
+        let pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        let mut fund = Self::get_fund(&env, &pool_id);
+        if token == pool.token_a {
+            fund.balance_a += amount;
+        } else if token == pool.token_b {
+            fund.balance_b += amount;
+        } else {
+            return Err(LiquidityError::InvalidPoolId);
+        }
+        
+        TokenClient::new(&env, &token).transfer(&funder, &env.current_contract_address(), &amount);
+        Self::adjust_token_reserves(&env, &token, amount);
+        env.storage().persistent().set(&DataKey::ProtectionFund(pool_id), &fund);
+        Ok(())
+    }
+
+    /// Protection fund balances and their coverage of pool liquidity
+    pub fn get_protection_fund(env: Env, pool_id: String) -> Result<ProtectionFundStatus, LiquidityError> {
+        let pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        let fund = Self::get_fund(&env, &pool_id);
+        
+        let fund_value_usd = Self::pair_value_usd(&env, &pool, fund.balance_a, fund.balance_b)
+            .ok_or(LiquidityError::PriceUnavailable)?;
+        let pool_value_usd = Self::pair_value_usd(&env, &pool, pool.reserve_a, pool.reserve_b)
+            .ok_or(LiquidityError::PriceUnavailable)?;
+        let coverage_ratio_bps = if pool_value_usd > 0 {
+            Self::mul_div(fund_value_usd, BASIS_POINTS, pool_value_usd)?.min(u32::MAX as i128) as u32
+        } else {
+            0
+        };
+        
+        Ok(ProtectionFundStatus {
+            pool_id,
+            balance_a: fund.balance_a,
+            balance_b: fund.balance_b,
+            fund_value_usd,
+            pool_value_usd,
+            coverage_ratio_bps,
+            total_compensated_usd: fund.total_compensated_usd,
+        })
+    }
+
+    /// Map a token to its oracle asset symbol (admin only)
+    pub fn set_price_feed(
+        env: Env,
+        caller: Address,
+        token: Address,
+        asset: String,
+    ) -> Result<(), LiquidityError> {
+        Self::require_admin(&env, &caller)?;
+        
+        env.storage().persistent().set(&DataKey::PriceFeed(token), &asset);
+        Ok(())
+    }
+
+    /// Create a pool for a token pair (admin, or the swap contract on behalf of anyone)
+    pub fn create_pool(
+        env: Env,
+        caller: Address,
+        pool_id: String,
+        token_a: Address,
+        token_b: Address,
+    ) -> Result<(), LiquidityError> {
+        caller.require_auth();
+        
+        let admin: Address = env.storage().instance()
+            .get(&DataKey::Admin)
+            .ok_or(LiquidityError::UnauthorizedAccess)?;
+        let swap_contract: Option<Address> = env.storage().instance().get(&DataKey::SwapContract);
+        
+        if caller != admin && Some(caller.clone()) != swap_contract {
+            return Err(LiquidityError::UnauthorizedAccess);
+        }
+        
+        if pool_id.is_empty() || token_a == token_b {
+            return Err(LiquidityError::InvalidPoolId);
+        }
+        
+        let pool_key = DataKey::Pool(pool_id.clone());
+        if env.storage().persistent().has(&pool_key) {
+            return Err(LiquidityError::PoolAlreadyExists);
+        }
+        
+        // Deploy the pool's LP share token at an address derived from the pool ID
+        let wasm_hash: BytesN<32> = env.storage().instance()
+            .get(&DataKey::LpTokenWasm)
+            .ok_or(LiquidityError::LpTokenNotConfigured)?;
+        let salt = env.crypto().sha256(&pool_id.clone().to_xdr(&env));
+        let lp_token = Self::deploy_lp_token(&env, wasm_hash, salt.to_bytes());
+        LpTokenClient::new(&env, &lp_token).initialize(&env.current_contract_address(), &pool_id);
+        
+        let pool = LiquidityPool {
+            pool_id,
+            token_a,
+            token_b,
+            reserve_a: 0,
+            reserve_b: 0,
+            total_lp_tokens: 0,
+            lp_token,
+            fee_growth_a: 0,
+            fee_growth_b: 0,
+            current_apy: 0,
+            created_at: env.ledger().timestamp(),
+            last_reward_distribution: env.ledger().timestamp(),
+            total_rewards_distributed: 0,
+            active: true,
+        };
+        
+        env.storage().persistent().set(&pool_key, &pool);
+        
+        let mut stats = Self::load_global_stats(&env);
+        stats.total_pools += 1;
+        env.storage().instance().set(&DataKey::GlobalStats, &stats);
+        Ok(())
+    }
+
+    /// Get pool reserves for swap pricing
+    pub fn get_reserves(env: Env, pool_id: String) -> Result<PoolReserves, LiquidityError> {
+        let pool = Self::get_pool(env, pool_id)?;
+        
+        Ok(PoolReserves {
+            token_a: pool.token_a,
+            token_b: pool.token_b,
+            reserve_a: pool.reserve_a,
+            reserve_b: pool.reserve_b,
+        })
+    }
+
+    /// Settle one swap hop against pool reserves (swap contract only).
+    /// `amount_in` must already have been transferred to this contract; the fee stays in the pool.
+    pub fn swap(
+        env: Env,
+        pool_id: String,
+        token_in: Address,
+        amount_in: i128,
+        fee: i128,
+        amount_out: i128,
+        to: Address,
+    ) -> Result<(), LiquidityError> {
+        Self::require_swap_contract(&env)?;
+        
+        if amount_in <= 0 || amount_out <= 0 || fee < 0 || fee >= amount_in {
+            return Err(LiquidityError::InvalidAmount);
+        }
+        
+        let mut pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        if !pool.active {
+            return Err(LiquidityError::PoolNotFound);
+        }
+        
+        let a_to_b = if token_in == pool.token_a {
+            true
+        } else if token_in == pool.token_b {
+            false
+        } else {
+            return Err(LiquidityError::InvalidPoolId);
+        };
+        
+        let (reserve_in, reserve_out, token_out) = if a_to_b {
+            (pool.reserve_a, pool.reserve_b, pool.token_b.clone())
+        } else {
+            (pool.reserve_b, pool.reserve_a, pool.token_a.clone())
+        };
+        
+        if amount_out >= reserve_out {
+            return Err(LiquidityError::InsufficientLiquidity);
+        }
+        
+        // Constant product must hold on the post-fee input
+        if (reserve_in + amount_in - fee) * (reserve_out - amount_out) < reserve_in * reserve_out {
+            return Err(LiquidityError::InsufficientLiquidity);
+        }
+        
+        // The input must actually have arrived on top of what the pools already hold
+        let contract = env.current_contract_address();
+        let held = TokenClient::new(&env, &token_in).balance(&contract);
+        if held < Self::get_token_reserves(&env, &token_in) + amount_in {
+            return Err(LiquidityError::InsufficientLiquidity);
+        }
+        Self::adjust_token_reserves(&env, &token_in, amount_in);
+        Self::adjust_token_reserves(&env, &token_out, -amount_out);
+        
+        // A slice of the fee funds IL protection; the rest stays in the pool and accrues to LP shares
+        let config = Self::get_protection_config(env.clone());
+        let fund_fee = fee * config.fee_share_bps as i128 / BASIS_POINTS;
+        let lp_fee = fee - fund_fee;
+        let fee_growth = if pool.total_lp_tokens > 0 {
+            Self::mul_div(lp_fee, FEE_GROWTH_PRECISION, pool.total_lp_tokens)?
+        } else {
+            0
+        };
+        
+        if fund_fee > 0 {
+            let mut fund = Self::get_fund(&env, &pool_id);
+            if a_to_b {
+                fund.balance_a += fund_fee;
+            } else {
+                fund.balance_b += fund_fee;
+            }
+            env.storage().persistent().set(&DataKey::ProtectionFund(pool_id.clone()), &fund);
+        }
+        
+        if a_to_b {
+            pool.reserve_a += amount_in - fund_fee;
+            pool.reserve_b -= amount_out;
+            pool.fee_growth_a += fee_growth;
+        } else {
+            pool.reserve_b += amount_in - fund_fee;
+            pool.reserve_a -= amount_out;
+            pool.fee_growth_b += fee_growth;
+        }
+        env.storage().persistent().set(&DataKey::Pool(pool_id.clone()), &pool);
+        Self::record_daily_fees(&env, &pool_id, a_to_b, amount_in, fee);
+        
+        // Intermediate hops keep their output here for the next hop
+        if to != contract {
+            TokenClient::new(&env, &token_out).transfer(&contract, &to, &amount_out);
+        }
+        
+        Ok(())
+    }
+
+    /// Recompute a pool's APY from its trailing fees and current emissions, and its share
+    /// of global stats at current prices; callable by anyone
+    pub fn refresh_apy(env: Env, pool_id: String) -> Result<u32, LiquidityError> {
+        let mut pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        
+        pool.current_apy = Self::calculate_dynamic_apy(&env, &pool)?;
+        env.storage().persistent().set(&DataKey::Pool(pool_id), &pool);
+        Self::update_global_stats(&env, &pool);
+        
+        Ok(pool.current_apy)
+    }
+
+    /// Daily fee snapshots of a pool within the trailing APY window, oldest first
+    pub fn get_fee_history(env: Env, pool_id: String) -> Vec<DailySnapshot> {
+        let today = env.ledger().timestamp() / SECONDS_PER_DAY;
+        let snapshots = Self::load_fee_snapshots(&env, &pool_id);
+        
+        let mut history = Vec::new(&env);
+        for offset in (0..FEE_SNAPSHOT_DAYS).rev() {
+            let Some(day) = today.checked_sub(offset) else {
+                continue;
+            };
+            let snapshot = snapshots.get_unchecked((day % FEE_SNAPSHOT_DAYS) as u32);
+            if snapshot.day == day {
+                history.push_back(snapshot);
+            }
+        }
+        history
+    }
+
+    /// Compare tracked reserves of a token with the balance actually held
+    pub fn reconcile_reserves(env: Env, token: Address) -> ReserveReconciliation {
+        ReserveReconciliation {
+            tracked_reserves: Self::get_token_reserves(&env, &token),
+            actual_balance: TokenClient::new(&env, &token).balance(&env.current_contract_address()),
+            token,
+        }
+    }
+
+    fn get_token_reserves(env: &Env, token: &Address) -> i128 {
+        env.storage().persistent()
+            .get(&DataKey::TokenReserves(token.clone()))
+            .unwrap_or(0)
+    }
+
+    fn adjust_token_reserves(env: &Env, token: &Address, delta: i128) {
+        let reserves = Self::get_token_reserves(env, token) + delta;
+        env.storage().persistent().set(&DataKey::TokenReserves(token.clone()), &reserves);
+    }
+
+    #[cfg(not(any(test, feature = "testutils")))]
+    fn deploy_lp_token(env: &Env, wasm_hash: BytesN<32>, salt: BytesN<32>) -> Address {
+        env.deployer().with_current_contract(salt).deploy_v2(wasm_hash, ())
+    }
+
+    /// Test builds have no LP token wasm, so the native contract is registered at the deploy address
+    #[cfg(any(test, feature = "testutils"))]
+    fn deploy_lp_token(env: &Env, _wasm_hash: BytesN<32>, salt: BytesN<32>) -> Address {
+        let address = env.deployer().with_current_contract(salt).deployed_address();
+        env.register_at(&address, swave_lp_token::LpTokenContract, ())
+    }
+
+    /// Require authorization from the admin
+    fn require_admin(env: &Env, caller: &Address) -> Result<(), LiquidityError> {
+        caller.require_auth();
+        
+        let admin: Address = env.storage().instance()
+            .get(&DataKey::Admin)
+            .ok_or(LiquidityError::UnauthorizedAccess)?;
+        
+        if *caller != admin {
+            return Err(LiquidityError::UnauthorizedAccess);
+        }
+        Ok(())
+    }
+
+    /// Require authorization from the configured swap contract
+    fn require_swap_contract(env: &Env) -> Result<(), LiquidityError> {
+        let swap_contract: Address = env.storage().instance()
+            .get(&DataKey::SwapContract)
+            .ok_or(LiquidityError::UnauthorizedAccess)?;
+        
+        swap_contract.require_auth();
+        Ok(())
+    }
+
+    /// Calculate LP tokens to issue
+    fn calculate_lp_tokens(
+        pool: &LiquidityPool,
+        token_a_amount: i128,
+        token_b_amount: i128,
+    ) -> Result<i128, LiquidityError> {
+        let lp_tokens = if pool.total_lp_tokens == 0 {
+            // First liquidity provision: geometric mean, less the permanently locked shares
+            let product = token_a_amount.checked_mul(token_b_amount)
+                .ok_or(LiquidityError::InvalidAmount)?;
+            Self::isqrt(product) - MINIMUM_LIQUIDITY
+        } else {
+            // Proportional to existing pool
+            let lp_a = Self::mul_div(token_a_amount, pool.total_lp_tokens, pool.reserve_a)?;
+            let lp_b = Self::mul_div(token_b_amount, pool.total_lp_tokens, pool.reserve_b)?;
+            lp_a.min(lp_b)
+        };
+        
+        // Deposits too small to earn a share would only donate to existing LPs
+        if lp_tokens <= 0 {
+            return Err(LiquidityError::InsufficientLiquidity);
+        }
+        
+        Ok(lp_tokens)
+    }
+
+    /// Largest amounts not exceeding the offered ones that match the pool ratio
+    fn calculate_deposit_amounts(
+        pool: &LiquidityPool,
+        token_a_amount: i128,
+        token_b_amount: i128,
+    ) -> Result<(i128, i128), LiquidityError> {
+        if pool.reserve_a == 0 || pool.reserve_b == 0 {
+            return Ok((token_a_amount, token_b_amount));
+        }
+        
+        let optimal_b = Self::mul_div(token_a_amount, pool.reserve_b, pool.reserve_a)?;
+        if optimal_b <= token_b_amount {
+            return Ok((token_a_amount, optimal_b));
+        }
+        
+        let optimal_a = Self::mul_div(token_b_amount, pool.reserve_a, pool.reserve_b)?;
+        Ok((optimal_a, token_b_amount))
+    }
+
+    /// Integer square root (floor)
+    fn isqrt(value: i128) -> i128 {
+        if value < 2 {
+            return value;
+        }
+        
+        let mut x = value;
+        let mut y = value / 2 + value % 2;
+        while y < x {
+            x = y;
+            y = (x + value / x) / 2;
+        }
+        x
+    }
+
+    /// `a * b / c` rounded down, failing instead of overflowing
+    fn mul_div(a: i128, b: i128, c: i128) -> Result<i128, LiquidityError> {
+        a.checked_mul(b)
+            .and_then(|product| product.checked_div(c))
+            .ok_or(LiquidityError::InvalidAmount)
+    }
+
+    /// Calculate withdrawal amounts
+    fn calculate_withdrawal_amounts(
+        pool: &LiquidityPool,
+        lp_tokens: i128,
+    ) -> Result<(i128, i128), LiquidityError> {
+        if pool.total_lp_tokens == 0 {
+            return Err(LiquidityError::InsufficientLiquidity);
+        }
+        
+        let token_a = Self::mul_div(lp_tokens, pool.reserve_a, pool.total_lp_tokens)?;
+        let token_b = Self::mul_div(lp_tokens, pool.reserve_b, pool.total_lp_tokens)?;
+        
+        Ok((token_a, token_b))
+    }
+
+    /// Dynamic APY: trailing 7-day swap fees over TVL plus annualized pool emissions (basis points)
+    fn calculate_dynamic_apy(env: &Env, pool: &LiquidityPool) -> Result<u32, LiquidityError> {
+        if pool.reserve_a == 0 || pool.reserve_b == 0 {
+            return Ok(0);
+        }
+        
+        // Trailing fees, with token B fees valued at the pool price so fees and TVL share a unit
+        let (mut fees_a, mut fees_b) = (0i128, 0i128);
+        for snapshot in Self::get_fee_history(env.clone(), pool.pool_id.clone()).iter() {
+            fees_a += snapshot.fees_a;
+            fees_b += snapshot.fees_b;
+        }
+        let fees = fees_a + Self::mul_div(fees_b, pool.reserve_a, pool.reserve_b)?;
+        let tvl = pool.reserve_a.saturating_mul(2);
+        
+        // The snapshots cover today so far plus the previous full days; young pools annualize their whole life
+        let now = env.ledger().timestamp();
+        let window = now.saturating_sub(pool.created_at)
+            .min((FEE_SNAPSHOT_DAYS - 1) * SECONDS_PER_DAY + now % SECONDS_PER_DAY)
+            .max(SECONDS_PER_DAY);
+        let annual_fees = Self::mul_div(fees, SECONDS_PER_YEAR, window as i128)?;
+        let fee_apy = Self::mul_div(annual_fees, BASIS_POINTS, tvl)?;
+        
+        // Emissions need oracle prices for the reward token and the pool; without them only fees count
+        let emission_apy = Self::calculate_emission_apy(env, pool).unwrap_or(0);
+        
+        Ok((fee_apy + emission_apy).clamp(0, MAX_APY as i128) as u32)
+    }
+
+    /// Annualized pool emissions over pool TVL at oracle prices (basis points)
+    fn calculate_emission_apy(env: &Env, pool: &LiquidityPool) -> Option<i128> {
+        let rewards = Self::accrue_pool_rewards(env, Self::load_pool_rewards(env, &pool.pool_id));
+        let reward_token = rewards.reward_token?;
+        if rewards.emission_rate <= 0 || rewards.reward_reserve <= 0 {
+            return Some(0);
+        }
+        
+        let annual_usd = Self::token_value_usd(env, &reward_token, rewards.emission_rate.checked_mul(SECONDS_PER_YEAR)?)?;
+        let tvl_usd = Self::pair_value_usd(env, pool, pool.reserve_a, pool.reserve_b)?;
+        if tvl_usd <= 0 {
+            return None;
+        }
+        
+        annual_usd.checked_mul(BASIS_POINTS)?.checked_div(tvl_usd)
+    }
+
+    /// Add a swap's fee and volume to today's snapshot, recycling the slot of the day it replaces
+    fn record_daily_fees(env: &Env, pool_id: &String, a_to_b: bool, amount_in: i128, fee: i128) {
+        let day = env.ledger().timestamp() / SECONDS_PER_DAY;
+        let slot = (day % FEE_SNAPSHOT_DAYS) as u32;
+        let mut snapshots = Self::load_fee_snapshots(env, pool_id);
+        
+        let mut snapshot = snapshots.get_unchecked(slot);
+        if snapshot.day != day {
+            snapshot = Self::empty_snapshot(day);
+        }
+        if a_to_b {
+            snapshot.fees_a += fee;
+            snapshot.volume_a += amount_in;
+        } else {
+            snapshot.fees_b += fee;
+            snapshot.volume_b += amount_in;
+        }
+        
+        snapshots.set(slot, snapshot);
+        env.storage().persistent().set(&DataKey::FeeSnapshots(pool_id.clone()), &snapshots);
+    }
+
+    fn load_fee_snapshots(env: &Env, pool_id: &String) -> Vec<DailySnapshot> {
+        env.storage().persistent()
+            .get(&DataKey::FeeSnapshots(pool_id.clone()))
+            .unwrap_or_else(|| {
+                let mut snapshots = Vec::new(env);
+                for _ in 0..FEE_SNAPSHOT_DAYS {
+                    snapshots.push_back(Self::empty_snapshot(0));
+                }
+                snapshots
+            })
+    }
+
+    fn empty_snapshot(day: u64) -> DailySnapshot {
+        DailySnapshot {
+            day,
+            fees_a: 0,
+            fees_b: 0,
+            volume_a: 0,
+            volume_b: 0,
+        }
+    }
+
+    /// Value lost against simply holding the deposit, at current oracle prices (USD).
+    /// `principal_*` are the LP's redeemable amounts excluding earned fees.
+    fn calculate_impermanent_loss(
+        env: &Env,
+        pool: &LiquidityPool,
+        hold_a: i128,
+        hold_b: i128,
+        principal_a: i128,
+        principal_b: i128,
+    ) -> Option<i128> {
+        let hold_value = Self::pair_value_usd(env, pool, hold_a, hold_b)?;
+        
+        // Value the principal as if the pool sat at the oracle price: 2 * sqrt(value_a * value_b).
+        // Trades only move the principal along its constant product, so pushing the pool price
+        // away from the oracle right before withdrawing cannot inflate the measured loss.
+        let value_a = Self::token_value_usd(env, &pool.token_a, principal_a)?;
+        let value_b = Self::token_value_usd(env, &pool.token_b, principal_b)?;
+        let lp_value = Self::isqrt(value_a.checked_mul(value_b)?.max(0)).checked_mul(2)?;
+        
+        Some((hold_value - lp_value).max(0))
+    }
+
+    /// Pay vested IL coverage from the pool's protection fund; returns the USD value paid.
+    /// The payout cap applies to everything the position was ever paid, so split withdrawals gain nothing.
+    fn pay_il_compensation(
+        env: &Env,
+        pool: &LiquidityPool,
+        position: &LiquidityPosition,
+        impermanent_loss: i128,
+    ) -> Result<i128, LiquidityError> {
+        let config = Self::get_protection_config(env.clone());
+        if impermanent_loss <= 0 || config.max_coverage_bps == 0 {
+            return Ok(0);
+        }
+        
+        // Coverage vests linearly with position age
+        let age = env.ledger().timestamp().saturating_sub(position.deposited_at).min(config.vesting_period);
+        let coverage_bps = config.max_coverage_bps as i128 * age as i128 / config.vesting_period as i128;
+        let remaining_cap = config.max_payout_usd.saturating_sub(position.il_compensated);
+        let mut owed = (impermanent_loss * coverage_bps / BASIS_POINTS).min(remaining_cap);
+        if owed <= 0 {
+            return Ok(0);
+        }
+        
+        // Pay in token A first, then token B, as far as the fund reaches
+        let mut fund = Self::get_fund(env, &pool.pool_id);
+        let mut paid = 0;
+        let contract = env.current_contract_address();
+        for (token, balance) in [(&pool.token_a, &mut fund.balance_a), (&pool.token_b, &mut fund.balance_b)] {
+            if owed <= 0 || *balance <= 0 {
+                continue;
+            }
+            let (price, unit) = match Self::token_price(env, token) {
+                Some(quote) => quote,
+                None => continue,
+            };
+            let amount = Self::mul_div(owed, unit, price)?.min(*balance);
+            if amount <= 0 {
+                continue;
+            }
+            let value = Self::mul_div(amount, price, unit)?;
+            
+            *balance -= amount;
+            owed -= value;
+            paid += value;
+            TokenClient::new(env, token).transfer(&contract, &position.provider, &amount);
+            Self::adjust_token_reserves(env, token, -amount);
+        }
+        
+        fund.total_compensated_usd += paid;
+        env.storage().persistent().set(&DataKey::ProtectionFund(pool.pool_id.clone()), &fund);
+        Ok(paid)
+    }
+
+    fn get_fund(env: &Env, pool_id: &String) -> ProtectionFund {
+        env.storage().persistent()
+            .get(&DataKey::ProtectionFund(pool_id.clone()))
+            .unwrap_or(ProtectionFund {
+                balance_a: 0,
+                balance_b: 0,
+                total_compensated_usd: 0,
+            })
+    }
+
+    /// Fees accrued to `shares` of a position since it was deposited
+    fn calculate_pending_fees(
+        pool: &LiquidityPool,
+        position: &LiquidityPosition,
+        shares: i128,
+    ) -> Result<(i128, i128), LiquidityError> {
+        let fee_a = Self::mul_div(shares, pool.fee_growth_a - position.fee_growth_entry_a, FEE_GROWTH_PRECISION)?;
+        let fee_b = Self::mul_div(shares, pool.fee_growth_b - position.fee_growth_entry_b, FEE_GROWTH_PRECISION)?;
+        Ok((fee_a, fee_b))
+    }
+
+    /// USD value of a pair of pool token amounts, if both tokens are priced
+    fn pair_value_usd(env: &Env, pool: &LiquidityPool, amount_a: i128, amount_b: i128) -> Option<i128> {
+        let value_a = Self::token_value_usd(env, &pool.token_a, amount_a)?;
+        let value_b = Self::token_value_usd(env, &pool.token_b, amount_b)?;
+        Some(value_a + value_b)
+    }
+
+    /// USD value (8 decimals) of a token amount from the oracle
+    fn token_value_usd(env: &Env, token: &Address, amount: i128) -> Option<i128> {
+        let (price, unit) = Self::token_price(env, token)?;
+        amount.checked_mul(price)?.checked_div(unit)
+    }
+
+    /// Oracle USD price (8 decimals) of one whole token, with the token's base unit
+    fn token_price(env: &Env, token: &Address) -> Option<(i128, i128)> {
+        let oracle: Address = env.storage().instance().get(&DataKey::Oracle)?;
+        let asset: String = env.storage().persistent().get(&DataKey::PriceFeed(token.clone()))?;
+        
+        let price = OracleClient::new(env, &oracle)
+            .try_get_price(&asset)
+            .ok()?
+            .ok()?
+            .price;
+        if price <= 0 {
+            return None;
+        }
+        let unit = 10i128.checked_pow(TokenClient::new(env, token).decimals())?;
+        
+        Some((price, unit))
+    }
+
+    /// Update liquidity position
+    fn update_liquidity_position(
+        env: &Env,
+        provider: &Address,
+        pool: &LiquidityPool,
+        lp_tokens: i128,
+        token_a: i128,
+        token_b: i128,
+    ) -> Result<(), LiquidityError> {
+        let position_key = DataKey::Position(provider.clone(), pool.pool_id.clone());
+        let mut position = Self::load_position(env, provider, pool);
+        
+        // Average the fee growth entry so pending fees are unchanged
+        let total = position.lp_tokens.saturating_add(lp_tokens);
+        position.fee_growth_entry_a = Self::average_entry(position.fee_growth_entry_a, position.lp_tokens, pool.fee_growth_a, lp_tokens)?;
+        position.fee_growth_entry_b = Self::average_entry(position.fee_growth_entry_b, position.lp_tokens, pool.fee_growth_b, lp_tokens)?;
+        // Top-ups restart IL coverage vesting in proportion to the shares added
+        position.deposited_at = Self::average_entry(
+            position.deposited_at as i128, position.lp_tokens, env.ledger().timestamp() as i128, lp_tokens,
+        )? as u64;
+        Self::track_provider(env, provider, position.lp_tokens, total);
+        position.lp_tokens = total;
+        position.initial_token_a = position.initial_token_a.saturating_add(token_a);
+        position.initial_token_b = position.initial_token_b.saturating_add(token_b);
+        
+        env.storage().persistent().set(&position_key, &position);
+        
+        Ok(())
+    }
+
+    /// A provider's position in a pool, or an empty one if they have none yet
+    fn load_position(env: &Env, provider: &Address, pool: &LiquidityPool) -> LiquidityPosition {
+        env.storage().persistent()
+            .get(&DataKey::Position(provider.clone(), pool.pool_id.clone()))
+            .unwrap_or(LiquidityPosition {
+                provider: provider.clone(),
+                pool_id: pool.pool_id.clone(),
+                lp_tokens: 0,
+                deposited_at: env.ledger().timestamp(),
+                initial_token_a: 0,
+                initial_token_b: 0,
+                accumulated_rewards: 0,
+                last_reward_claim: env.ledger().timestamp(),
+                impermanent_loss: 0,
+                fee_growth_entry_a: pool.fee_growth_a,
+                fee_growth_entry_b: pool.fee_growth_b,
+                fees_earned_a: 0,
+                fees_earned_b: 0,
+                il_compensated: 0,
+                reward_stake: 0,
+                reward_weight: 0,
+                reward_debt: 0,
+                unclaimed_rewards: 0,
+                locked_shares: 0,
+                lock_until: 0,
+                lock_duration: 0,
+                lock_multiplier: BASIS_POINTS as u32,
+            })
+    }
+
+    /// Settle a position's accrued rewards and change its reward stake by `stake_delta`.
+    /// The stake is always backed by LP shares this contract holds for the position.
+    fn sync_rewards(
+        env: &Env,
+        pool: &LiquidityPool,
+        position: &mut LiquidityPosition,
+        stake_delta: i128,
+    ) -> Result<PoolRewards, LiquidityError> {
+        let mut rewards = Self::update_pool_rewards(env, &pool.pool_id);
+        
+        let accrued = Self::mul_div(position.reward_weight, rewards.acc_reward_per_share, REWARD_PRECISION)?;
+        position.unclaimed_rewards += accrued - position.reward_debt;
+        
+        let new_stake = position.reward_stake + stake_delta;
+        if new_stake < 0 {
+            return Err(LiquidityError::InsufficientLiquidity);
+        }
+        let new_weight = Self::reward_weight(env, position, new_stake)?;
+        
+        // Every campaign accrues on the same stake, so settle each one at the old stake.
+        // A campaign whose accounting fails is skipped rather than blocking the position.
+        for id in Self::get_campaign_ids(env, &pool.pool_id).iter() {
+            let _ = Self::settle_campaign(env, id, position, new_weight, rewards.total_staked);
+        }
+        
+        rewards.total_staked += new_weight - position.reward_weight;
+        position.reward_stake = new_stake;
+        position.reward_weight = new_weight;
+        position.reward_debt = Self::mul_div(new_weight, rewards.acc_reward_per_share, REWARD_PRECISION)?;
+        
+        env.storage().persistent().set(&DataKey::PoolRewards(pool.pool_id.clone()), &rewards);
+        Ok(rewards)
+    }
+
+    /// Settle a position's rewards in one campaign and move its debt to `new_weight`
+    fn settle_campaign(
+        env: &Env,
+        campaign_id: u32,
+        position: &LiquidityPosition,
+        new_weight: i128,
+        total_staked: i128,
+    ) -> Result<(), LiquidityError> {
+        let campaign = Self::accrue_campaign(env, Self::load_campaign(env, campaign_id)?, total_staked);
+        let mut standing = Self::load_campaign_position(env, campaign_id, &position.provider);
+        
+        let accrued = Self::mul_div(position.reward_weight, campaign.acc_reward_per_share, REWARD_PRECISION)?;
+        standing.unclaimed += accrued - standing.reward_debt;
+        standing.reward_debt = Self::mul_div(new_weight, campaign.acc_reward_per_share, REWARD_PRECISION)?;
+        
+        env.storage().persistent().set(&DataKey::Campaign(campaign_id), &campaign);
+        env.storage().persistent().set(&DataKey::CampaignPosition(campaign_id, position.provider.clone()), &standing);
+        Ok(())
+    }
+
+    /// Reward weight of a stake: locked shares count with the lock multiplier until the lock ends
+    fn reward_weight(env: &Env, position: &LiquidityPosition, stake: i128) -> Result<i128, LiquidityError> {
+        let locked = position.locked_shares.min(stake);
+        if locked == 0 || env.ledger().timestamp() >= position.lock_until {
+            return Ok(stake);
+        }
+        
+        Ok(stake - locked + Self::mul_div(locked, position.lock_multiplier as i128, BASIS_POINTS)?)
+    }
+
+    /// Duration and reward multiplier of a lock tier
+    fn lock_tier(months: u32) -> Result<(u64, u32), LiquidityError> {
+        LOCK_TIERS.iter()
+            .find(|(tier, _, _)| *tier == months)
+            .map(|(_, duration, multiplier)| (*duration, *multiplier))
+            .ok_or(LiquidityError::InvalidLockPeriod)
+    }
+
+    /// Accrue a pool's emissions up to now and store them
+    fn update_pool_rewards(env: &Env, pool_id: &String) -> PoolRewards {
+        let rewards = Self::accrue_pool_rewards(env, Self::load_pool_rewards(env, pool_id));
+        env.storage().persistent().set(&DataKey::PoolRewards(pool_id.clone()), &rewards);
+        rewards
+    }
+
+    /// Emit rewards for the time elapsed since the last update, limited by the funded reserve
+    fn accrue_pool_rewards(env: &Env, mut rewards: PoolRewards) -> PoolRewards {
+        let now = env.ledger().timestamp();
+        if now <= rewards.last_update {
+            return rewards;
+        }
+        
+        if rewards.total_staked > 0 {
+            let elapsed = (now - rewards.last_update) as i128;
+            let emitted = elapsed.saturating_mul(rewards.emission_rate).min(rewards.reward_reserve);
+            rewards.acc_reward_per_share += emitted.saturating_mul(REWARD_PRECISION) / rewards.total_staked;
+            rewards.reward_reserve -= emitted;
+        }
+        rewards.last_update = now;
+        rewards
+    }
+
+    /// Emit a campaign's rewards for the part of its window elapsed since the last update
+    fn accrue_campaign(env: &Env, mut campaign: RewardCampaign, total_staked: i128) -> RewardCampaign {
+        let now = env.ledger().timestamp().min(campaign.end);
+        if now <= campaign.last_update {
+            return campaign;
+        }
+        
+        // Time with nothing staked emits nothing; the creator can withdraw it after the end
+        if total_staked > 0 {
+            let elapsed = (now - campaign.last_update) as i128;
+            let duration = (campaign.end - campaign.start) as i128;
+            let emitted = (campaign.total_amount.saturating_mul(elapsed) / duration)
+                .min(campaign.total_amount - campaign.emitted);
+            campaign.acc_reward_per_share += emitted.saturating_mul(REWARD_PRECISION) / total_staked;
+            campaign.emitted += emitted;
+        }
+        campaign.last_update = now;
+        campaign
+    }
+
+    /// Return everything a campaign past its claim period still holds, unclaimed or never
+    /// emitted, to its creator; the campaign is being dropped from its pool and pays nothing more
+    fn close_campaign(env: &Env, mut campaign: RewardCampaign) {
+        let amount = campaign.total_amount - campaign.claimed;
+        campaign.total_amount = campaign.claimed;
+        campaign.emitted = campaign.claimed;
+        env.storage().persistent().set(&DataKey::Campaign(campaign.id), &campaign);
+        
+        if amount > 0 {
+            TokenClient::new(env, &campaign.reward_token).transfer(&env.current_contract_address(), &campaign.creator, &amount);
+            Self::adjust_token_reserves(env, &campaign.reward_token, -amount);
+        }
+    }
+
+    fn load_campaign(env: &Env, campaign_id: u32) -> Result<RewardCampaign, LiquidityError> {
+        env.storage().persistent()
+            .get(&DataKey::Campaign(campaign_id))
+            .ok_or(LiquidityError::DistributionFailed)
+    }
+
+    fn get_campaign_ids(env: &Env, pool_id: &String) -> Vec<u32> {
+        env.storage().persistent()
+            .get(&DataKey::PoolCampaigns(pool_id.clone()))
+            .unwrap_or(Vec::new(env))
+    }
+
+    fn load_campaign_position(env: &Env, campaign_id: u32, provider: &Address) -> CampaignPosition {
+        env.storage().persistent()
+            .get(&DataKey::CampaignPosition(campaign_id, provider.clone()))
+            .unwrap_or(CampaignPosition {
+                reward_debt: 0,
+                unclaimed: 0,
+                claimed: 0,
+            })
+    }
+
+    /// Add a campaign payout to the pool's distribution record for that campaign start
+    fn record_distribution(env: &Env, pool: &LiquidityPool, start: u64, amount: i128, new_participant: bool) {
+        let key = DataKey::Distribution(pool.pool_id.clone(), start);
+        let mut distribution = env.storage().persistent()
+            .get::<DataKey, RewardDistribution>(&key)
+            .unwrap_or(RewardDistribution {
+                pool_id: pool.pool_id.clone(),
+                total_rewards: 0,
+                participants: 0,
+                distributed_at: 0,
+                avg_reward: 0,
+                new_apy: pool.current_apy,
+            });
+        
+        distribution.total_rewards += amount;
+        if new_participant {
+            distribution.participants += 1;
+        }
+        distribution.distributed_at = env.ledger().timestamp();
+        distribution.avg_reward = distribution.total_rewards / distribution.participants.max(1) as i128;
+        distribution.new_apy = pool.current_apy;
+        env.storage().persistent().set(&key, &distribution);
+    }
+
+    fn load_pool_rewards(env: &Env, pool_id: &String) -> PoolRewards {
+        env.storage().persistent()
+            .get(&DataKey::PoolRewards(pool_id.clone()))
+            .unwrap_or(PoolRewards {
+                reward_token: None,
+                emission_rate: 0,
+                acc_reward_per_share: 0,
+                last_update: env.ledger().timestamp(),
+                total_staked: 0,
+                reward_reserve: 0,
+            })
+    }
+
+    /// Share-weighted average of two fee growth entries
+    fn average_entry(entry: i128, shares: i128, growth: i128, new_shares: i128) -> Result<i128, LiquidityError> {
+        let total = shares + new_shares;
+        if total == 0 {
+            return Ok(growth);
+        }
+        let weighted = entry.checked_mul(shares)
+            .and_then(|old| growth.checked_mul(new_shares).and_then(|new| old.checked_add(new)))
+            .ok_or(LiquidityError::InvalidAmount)?;
+        Ok(weighted / total)
+    }
+
+    /// Replace a pool's contribution to global TVL and average APY with its value at current prices
+    fn update_global_stats(env: &Env, pool: &LiquidityPool) {
+        let value_key = DataKey::PoolValue(pool.pool_id.clone());
+        let (old_tvl, old_apy): (i128, u32) = env.storage().persistent()
+            .get(&value_key)
+            .unwrap_or((0, 0));
+        
+        let tvl = Self::pair_value_usd(env, pool, pool.reserve_a, pool.reserve_b).unwrap_or(0);
+        let weighted_apy: i128 = env.storage().instance().get(&DataKey::TvlWeightedApy).unwrap_or(0);
+        let weighted_apy = weighted_apy
+            .saturating_add(tvl.saturating_mul(pool.current_apy as i128))
+            .saturating_sub(old_tvl.saturating_mul(old_apy as i128));
+        
+        let mut stats = Self::load_global_stats(env);
+        stats.total_tvl_usd = stats.total_tvl_usd.saturating_add(tvl - old_tvl);
+        stats.average_apy = if stats.total_tvl_usd > 0 {
+            (weighted_apy / stats.total_tvl_usd) as u32
+        } else {
+            0
+        };
+        
+        env.storage().persistent().set(&value_key, &(tvl, pool.current_apy));
+        env.storage().instance().set(&DataKey::TvlWeightedApy, &weighted_apy);
+        env.storage().instance().set(&DataKey::GlobalStats, &stats);
+    }
+
+    /// Count a provider as active while they hold at least one nonzero position
+    fn track_provider(env: &Env, provider: &Address, lp_before: i128, lp_after: i128) {
+        if (lp_before > 0) == (lp_after > 0) {
+            return;
+        }
+        
+        let key = DataKey::ProviderPools(provider.clone());
+        let pools: u32 = env.storage().persistent().get(&key).unwrap_or(0);
+        let mut stats = Self::load_global_stats(env);
+        
+        let pools = if lp_after > 0 {
+            if pools == 0 {
+                stats.active_providers += 1;
+            }
+            pools + 1
+        } else {
+            if pools == 1 {
+                stats.active_providers = stats.active_providers.saturating_sub(1);
+            }
+            pools.saturating_sub(1)
+        };
+        
+        env.storage().persistent().set(&key, &pools);
+        env.storage().instance().set(&DataKey::GlobalStats, &stats);
+    }
+
+    /// Add a reward payout to global stats at its current USD value
+    fn record_rewards_distributed(env: &Env, reward_token: &Address, amount: i128) {
+        let Some(value) = Self::token_value_usd(env, reward_token, amount) else {
+            return;
+        };
+        
+        let mut stats = Self::load_global_stats(env);
+        stats.total_rewards_distributed = stats.total_rewards_distributed.saturating_add(value);
+        env.storage().instance().set(&DataKey::GlobalStats, &stats);
+    }
+
+    fn load_global_stats(env: &Env) -> GlobalLiquidityStats {
+        env.storage().instance()
+            .get::<DataKey, GlobalLiquidityStats>(&DataKey::GlobalStats)
+            .unwrap_or(GlobalLiquidityStats {
+                total_tvl_usd: 0,
+                total_pools: 0,
+                active_providers: 0,
+                total_rewards_distributed: 0,
+                average_apy: 0,
+            })
+    }
+
+    /// Get pool information
+    pub fn get_pool(env: Env, pool_id: String) -> Result<LiquidityPool, LiquidityError> {
+        env.storage().persistent()
+            .get::<DataKey, LiquidityPool>(&DataKey::Pool(pool_id))
+            .ok_or(LiquidityError::PoolNotFound)
+    }
+
+    /// Get the LP share token of a pool
+    pub fn get_lp_token(env: Env, pool_id: String) -> Result<Address, LiquidityError> {
+        Ok(Self::get_pool(env, pool_id)?.lp_token)
+    }
+
+    /// Get user position
+    pub fn get_position(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+    ) -> Result<LiquidityPosition, LiquidityError> {
+        env.storage().persistent()
+            .get::<DataKey, LiquidityPosition>(&DataKey::Position(provider, pool_id))
+            .ok_or(LiquidityError::InsufficientLiquidity)
+    }
+
+    /// Fee income, rewards (campaigns included) and impermanent loss of a position at current oracle prices
+    pub fn get_position_pnl(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+    ) -> Result<PositionPnl, LiquidityError> {
+        let position = Self::get_position(env.clone(), provider.clone(), pool_id.clone())?;
+        let pool = Self::get_pool(env.clone(), pool_id.clone())?;
+        let pending_rewards = Self::get_pending_rewards(env.clone(), provider.clone(), pool_id.clone())?;
+        
+        let (pending_a, pending_b) = Self::calculate_pending_fees(&pool, &position, position.lp_tokens)?;
+        let fee_income_a = position.fees_earned_a + pending_a;
+        let fee_income_b = position.fees_earned_b + pending_b;
+        let fee_income_usd = Self::pair_value_usd(&env, &pool, fee_income_a, fee_income_b)
+            .ok_or(LiquidityError::PriceUnavailable)?;
+        
+        // Value the remaining shares as if they were withdrawn now
+        let (redeem_a, redeem_b) = if position.lp_tokens > 0 {
+            Self::calculate_withdrawal_amounts(&pool, position.lp_tokens)?
+        } else {
+            (0, 0)
+        };
+        let position_value_usd = Self::pair_value_usd(&env, &pool, redeem_a, redeem_b)
+            .ok_or(LiquidityError::PriceUnavailable)?;
+        let hold_value_usd = Self::pair_value_usd(&env, &pool, position.initial_token_a, position.initial_token_b)
+            .ok_or(LiquidityError::PriceUnavailable)?;
+        let unrealized_loss = Self::calculate_impermanent_loss(
+            &env, &pool,
+            position.initial_token_a, position.initial_token_b,
+            redeem_a - pending_a, redeem_b - pending_b,
+        ).ok_or(LiquidityError::PriceUnavailable)?;
+        
+        // Pool emissions and every campaign still tracked, each at its own token's price
+        let rewards = position.accumulated_rewards + pending_rewards;
+        let pool_rewards = Self::get_pool_rewards(env.clone(), pool_id.clone());
+        let mut rewards_usd = pool_rewards.reward_token
+            .and_then(|token| Self::token_value_usd(&env, &token, rewards))
+            .unwrap_or(0);
+        for id in Self::get_campaign_ids(&env, &pool_id).iter() {
+            let Ok(campaign) = Self::load_campaign(&env, id) else {
+                continue;
+            };
+            let campaign = Self::accrue_campaign(&env, campaign, pool_rewards.total_staked);
+            let standing = Self::load_campaign_position(&env, id, &provider);
+            let earned = Self::mul_div(position.reward_weight, campaign.acc_reward_per_share, REWARD_PRECISION)
+                .map(|accrued| standing.claimed + standing.unclaimed + accrued - standing.reward_debt)
+                .unwrap_or(standing.claimed + standing.unclaimed);
+            rewards_usd += Self::token_value_usd(&env, &campaign.reward_token, earned).unwrap_or(0);
+        }
+        
+        Ok(PositionPnl {
+            fee_income_a,
+            fee_income_b,
+            fee_income_usd,
+            rewards,
+            rewards_usd,
+            impermanent_loss_usd: position.impermanent_loss + unrealized_loss,
+            il_compensation_usd: position.il_compensated,
+            hold_value_usd,
+            position_value_usd,
+        })
+    }
+
+    /// Get global statistics
+    pub fn get_global_stats(env: Env) -> Result<GlobalLiquidityStats, LiquidityError> {
+        Ok(Self::load_global_stats(&env))
+    }
+} 
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,930 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::{
+    contract, contractimpl,
+    testutils::{Address as _, Ledger},
+    token::StellarAssetClient,
+};
+use swave_lp_token::LpTokenContractClient;
+
+const DEPOSIT: i128 = 10_000_000_000;       // 1,000 tokens at 7 decimals
+const USD: i128 = 100_000_000;              // $1 at 8 decimals
+
+/// Oracle returning prices set by the test
+#[contract]
+pub struct MockOracle;
+
+#[contractimpl]
+impl MockOracle {
+    pub fn set_price(env: Env, asset: String, price: i128) {
+        env.storage().instance().set(&asset, &price);
+    }
+
+    pub fn get_price(env: Env, asset: String) -> AggregatedPrice {
+        let price: i128 = env.storage().instance().get(&asset).unwrap();
+        AggregatedPrice {
+            asset,
+            price,
+            timestamp: env.ledger().timestamp(),
+            sources: Vec::new(&env),
+            confidence_score: 100,
+            twap_price: price,
+            deviation: 0,
+        }
+    }
+}
+
+struct Setup {
+    env: Env,
+    admin: Address,
+    contract: UltimateLiquidityContractClient<'static>,
+    pool_id: String,
+    token_a: Address,
+    token_b: Address,
+    lp_token: LpTokenContractClient<'static>,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.cost_estimate().budget().reset_unlimited();
+
+        let admin = Address::generate(&env);
+        let contract = UltimateLiquidityContractClient::new(&env, &env.register(UltimateLiquidityContract, ()));
+        contract.initialize(&admin);
+        contract.set_lp_token_wasm(&admin, &BytesN::from_array(&env, &[0; 32]));
+
+        let token_a = env.register_stellar_asset_contract_v2(admin.clone()).address();
+        let token_b = env.register_stellar_asset_contract_v2(admin.clone()).address();
+        let pool_id = String::from_str(&env, "A_B");
+        contract.create_pool(&admin, &pool_id, &token_a, &token_b);
+        let lp_token = LpTokenContractClient::new(&env, &contract.get_pool(&pool_id).lp_token);
+
+        Setup { env, admin, contract, pool_id, token_a, token_b, lp_token }
+    }
+
+    /// A new provider funded with `amount` of both pool tokens
+    fn provider(&self, amount: i128) -> Address {
+        let provider = Address::generate(&self.env);
+        self.mint(&self.token_a, &provider, amount);
+        self.mint(&self.token_b, &provider, amount);
+        provider
+    }
+
+    fn mint(&self, token: &Address, to: &Address, amount: i128) {
+        StellarAssetClient::new(&self.env, token).mint(to, &amount);
+    }
+
+    fn deposit(&self, provider: &Address, amount: i128) -> LiquidityDeposit {
+        self.contract.add_liquidity(provider, &self.pool_id, &amount, &amount, &0, &0, &u64::MAX)
+    }
+
+    /// Deposit and stake every minted share for rewards
+    fn deposit_staked(&self, provider: &Address, amount: i128) -> i128 {
+        let lp_tokens = self.deposit(provider, amount).lp_tokens;
+        self.contract.stake(provider, &self.pool_id, &lp_tokens);
+        lp_tokens
+    }
+
+    fn set_time(&self, timestamp: u64) {
+        self.env.ledger().with_mut(|ledger| ledger.timestamp = timestamp);
+    }
+
+    fn balance(&self, token: &Address, holder: &Address) -> i128 {
+        TokenClient::new(&self.env, token).balance(holder)
+    }
+
+    fn assert_reconciled(&self) {
+        for token in [&self.token_a, &self.token_b] {
+            let reconciliation = self.contract.reconcile_reserves(token);
+            assert_eq!(reconciliation.tracked_reserves, reconciliation.actual_balance);
+        }
+    }
+
+    /// Emit `rate` of a new reward token per second, funded with `reserve`
+    fn start_emissions(&self, rate: i128, reserve: i128) -> Address {
+        let reward_token = self.env.register_stellar_asset_contract_v2(self.admin.clone()).address();
+        self.contract.set_reward_emission(&self.admin, &self.pool_id, &reward_token, &rate);
+        self.mint(&reward_token, &self.admin, reserve);
+        self.contract.fund_rewards(&self.admin, &self.pool_id, &reserve);
+        reward_token
+    }
+
+    /// A partner funding a campaign of `amount` of a new reward token between `start` and `end`
+    fn start_campaign(&self, amount: i128, start: u64, end: u64) -> (Address, Address, u32) {
+        let creator = Address::generate(&self.env);
+        let reward_token = self.env.register_stellar_asset_contract_v2(self.admin.clone()).address();
+        self.mint(&reward_token, &creator, amount);
+        self.contract.set_campaign_token(&self.admin, &reward_token, &1);
+        let id = self.contract.create_campaign(&creator, &self.pool_id, &reward_token, &amount, &start, &end);
+        (creator, reward_token, id)
+    }
+
+    /// Price both pool tokens through a mock oracle
+    fn set_prices(&self, price_a: i128, price_b: i128) -> MockOracleClient<'static> {
+        let oracle = MockOracleClient::new(&self.env, &self.env.register(MockOracle, ()));
+        for (token, asset, price) in [(&self.token_a, "A", price_a), (&self.token_b, "B", price_b)] {
+            let asset = String::from_str(&self.env, asset);
+            oracle.set_price(&asset, &price);
+            self.contract.set_price_feed(&self.admin, token, &asset);
+        }
+        self.contract.set_oracle(&self.admin, &oracle.address);
+        oracle
+    }
+
+    /// Arbitrage after token A falls to a quarter of B: a pool of `DEPOSIT` each doubles its A and halves its B
+    fn crash_token_a(&self, oracle: &MockOracleClient) {
+        let swap_contract = Address::generate(&self.env);
+        self.contract.set_swap_contract(&self.admin, &swap_contract);
+        self.mint(&self.token_a, &self.contract.address, DEPOSIT);
+        self.contract.swap(&self.pool_id, &self.token_a, &DEPOSIT, &0, &(DEPOSIT / 2), &swap_contract);
+        oracle.set_price(&String::from_str(&self.env, "A"), &(USD / 4));
+    }
+}
+
+#[test]
+fn test_initialize_twice() {
+    let setup = Setup::new();
+
+    assert_eq!(
+        setup.contract.try_initialize(&setup.admin),
+        Err(Ok(LiquidityError::AlreadyInitialized))
+    );
+}
+
+#[test]
+fn test_operations_keyed_by_pool_id() {
+    let setup = Setup::new();
+    let provider = setup.provider(DEPOSIT);
+    let unknown = String::from_str(&setup.env, "A_C");
+
+    setup.deposit(&provider, DEPOSIT);
+    let position = setup.contract.get_position(&provider, &setup.pool_id);
+    assert_eq!(position.pool_id, setup.pool_id);
+    let pool = setup.contract.get_pool(&setup.pool_id);
+    assert_eq!((pool.reserve_a, pool.reserve_b), (DEPOSIT, DEPOSIT));
+    assert_eq!((pool.token_a, pool.token_b), (setup.token_a.clone(), setup.token_b.clone()));
+
+    // Nothing falls back to a default pool
+    assert_eq!(
+        setup.contract.try_add_liquidity(&provider, &unknown, &DEPOSIT, &DEPOSIT, &0, &0, &u64::MAX),
+        Err(Ok(LiquidityError::PoolNotFound))
+    );
+    assert_eq!(
+        setup.contract.try_remove_liquidity(&provider, &unknown, &position.lp_tokens, &0, &0, &u64::MAX),
+        Err(Ok(LiquidityError::PoolNotFound))
+    );
+    assert_eq!(setup.contract.try_get_pool(&unknown), Err(Ok(LiquidityError::PoolNotFound)));
+    assert_eq!(setup.contract.try_get_reserves(&unknown), Err(Ok(LiquidityError::PoolNotFound)));
+    assert_eq!(
+        setup.contract.try_get_position(&provider, &unknown),
+        Err(Ok(LiquidityError::InsufficientLiquidity))
+    );
+
+    let swap = Address::generate(&setup.env);
+    setup.contract.set_swap_contract(&setup.admin, &swap);
+    assert_eq!(
+        setup.contract.try_swap(&unknown, &setup.token_a, &1_000, &3, &900, &provider),
+        Err(Ok(LiquidityError::PoolNotFound))
+    );
+}
+
+#[test]
+fn test_deposits_held_in_custody() {
+    let setup = Setup::new();
+    let provider = setup.provider(DEPOSIT);
+
+    let lp_tokens = setup.deposit(&provider, DEPOSIT).lp_tokens;
+    assert_eq!(setup.balance(&setup.token_a, &provider), 0);
+    assert_eq!(setup.balance(&setup.token_b, &setup.contract.address), DEPOSIT);
+    setup.assert_reconciled();
+
+    // Deposits beyond the provider's balance fail before any state changes
+    assert!(setup.contract.try_add_liquidity(&provider, &setup.pool_id, &DEPOSIT, &DEPOSIT, &0, &0, &u64::MAX).is_err());
+
+    let (amount_a, amount_b) = setup.contract.remove_liquidity(
+        &provider, &setup.pool_id, &lp_tokens, &0, &0, &u64::MAX,
+    );
+    assert_eq!(setup.balance(&setup.token_a, &provider), amount_a);
+    assert_eq!(setup.balance(&setup.token_b, &provider), amount_b);
+    setup.assert_reconciled();
+
+    // A donation shows up as a surplus over tracked reserves
+    setup.mint(&setup.token_a, &setup.contract.address, 1_000);
+    let reconciliation = setup.contract.reconcile_reserves(&setup.token_a);
+    assert_eq!(reconciliation.actual_balance - reconciliation.tracked_reserves, 1_000);
+}
+
+#[test]
+fn test_lp_shares_minted_and_burned() {
+    let setup = Setup::new();
+    let provider = setup.provider(DEPOSIT);
+
+    // The first deposit mints the geometric mean, less the shares locked with the contract
+    let deposit = setup.deposit(&provider, DEPOSIT);
+    assert_eq!(deposit.lp_tokens, DEPOSIT - MINIMUM_LIQUIDITY);
+    assert_eq!(setup.lp_token.balance(&provider), deposit.lp_tokens);
+    assert_eq!(setup.lp_token.balance(&setup.contract.address), MINIMUM_LIQUIDITY);
+    assert_eq!(setup.lp_token.admin(), setup.contract.address);
+    let pool = setup.contract.get_pool(&setup.pool_id);
+    assert_eq!(pool.total_lp_tokens, DEPOSIT);
+    assert_eq!(setup.lp_token.total_supply(), pool.total_lp_tokens);
+
+    // Only the amounts matching the pool ratio are taken
+    let other = setup.provider(2 * DEPOSIT);
+    let deposit_2 = setup.contract.add_liquidity(
+        &other, &setup.pool_id, &DEPOSIT, &(2 * DEPOSIT), &0, &0, &u64::MAX,
+    );
+    assert_eq!((deposit_2.amount_a, deposit_2.amount_b, deposit_2.lp_tokens), (DEPOSIT, DEPOSIT, DEPOSIT));
+    assert_eq!(setup.balance(&setup.token_b, &other), DEPOSIT);
+    setup.assert_reconciled();
+
+    // Shares received by transfer redeem like any others
+    let holder = Address::generate(&setup.env);
+    setup.lp_token.transfer(&provider, &holder, &deposit.lp_tokens);
+    assert_eq!(
+        setup.contract.try_remove_liquidity(&provider, &setup.pool_id, &1, &0, &0, &u64::MAX),
+        Err(Ok(LiquidityError::InsufficientLiquidity))
+    );
+    let (amount_a, amount_b) = setup.contract.remove_liquidity(
+        &holder, &setup.pool_id, &deposit.lp_tokens, &0, &0, &u64::MAX,
+    );
+    assert_eq!((amount_a, amount_b), (deposit.lp_tokens, deposit.lp_tokens));
+    assert_eq!(setup.balance(&setup.token_a, &holder), amount_a);
+    assert_eq!(setup.lp_token.balance(&holder), 0);
+
+    let pool = setup.contract.get_pool(&setup.pool_id);
+    assert_eq!(pool.total_lp_tokens, DEPOSIT + MINIMUM_LIQUIDITY);
+    assert_eq!(setup.lp_token.total_supply(), pool.total_lp_tokens);
+    assert_eq!(pool.reserve_a, DEPOSIT + MINIMUM_LIQUIDITY);
+    setup.assert_reconciled();
+}
+
+#[test]
+fn test_deposit_and_withdrawal_bounds() {
+    let setup = Setup::new();
+    let provider = setup.provider(2 * DEPOSIT);
+    let deposit = setup.deposit(&provider, DEPOSIT);
+
+    setup.set_time(100);
+    assert_eq!(
+        setup.contract.try_add_liquidity(&provider, &setup.pool_id, &DEPOSIT, &DEPOSIT, &0, &0, &99),
+        Err(Ok(LiquidityError::DeadlineExpired))
+    );
+    assert_eq!(
+        setup.contract.try_add_liquidity(
+            &provider, &setup.pool_id, &DEPOSIT, &(DEPOSIT / 2), &DEPOSIT, &0, &u64::MAX,
+        ),
+        Err(Ok(LiquidityError::SlippageExceeded))
+    );
+    assert_eq!(
+        setup.contract.try_remove_liquidity(&provider, &setup.pool_id, &deposit.lp_tokens, &0, &0, &99),
+        Err(Ok(LiquidityError::DeadlineExpired))
+    );
+    assert_eq!(
+        setup.contract.try_remove_liquidity(
+            &provider, &setup.pool_id, &deposit.lp_tokens, &DEPOSIT, &0, &u64::MAX,
+        ),
+        Err(Ok(LiquidityError::SlippageExceeded))
+    );
+
+    // Nothing moved on the rejected calls
+    assert_eq!(setup.lp_token.balance(&provider), deposit.lp_tokens);
+    assert_eq!(setup.balance(&setup.token_a, &provider), DEPOSIT);
+    setup.assert_reconciled();
+}
+
+#[test]
+fn test_swap_settles_against_custody() {
+    let setup = Setup::new();
+    let swap_contract = Address::generate(&setup.env);
+    setup.contract.set_swap_contract(&setup.admin, &swap_contract);
+    let provider = setup.provider(DEPOSIT);
+    setup.deposit(&provider, DEPOSIT);
+
+    // Input that never arrived is rejected, as is output beyond the constant product
+    let trader = Address::generate(&setup.env);
+    let amount_in = 100_000_000;
+    let fee = 300_000;
+    let amount_out = 98_715_803;
+    assert_eq!(
+        setup.contract.try_swap(&setup.pool_id, &setup.token_a, &amount_in, &fee, &amount_out, &trader),
+        Err(Ok(LiquidityError::InsufficientLiquidity))
+    );
+    setup.mint(&setup.token_a, &setup.contract.address, amount_in);
+    assert_eq!(
+        setup.contract.try_swap(&setup.pool_id, &setup.token_a, &amount_in, &fee, &(amount_out + 1), &trader),
+        Err(Ok(LiquidityError::InsufficientLiquidity))
+    );
+
+    setup.contract.swap(&setup.pool_id, &setup.token_a, &amount_in, &fee, &amount_out, &trader);
+    assert_eq!(setup.balance(&setup.token_b, &trader), amount_out);
+    let pool = setup.contract.get_pool(&setup.pool_id);
+    assert_eq!((pool.reserve_a, pool.reserve_b), (DEPOSIT + amount_in, DEPOSIT - amount_out));
+    setup.assert_reconciled();
+}
+
+#[test]
+fn test_create_pool_rejects_duplicates_and_outsiders() {
+    let setup = Setup::new();
+    let env = &setup.env;
+    let token_c = env.register_stellar_asset_contract_v2(setup.admin.clone()).address();
+
+    assert_eq!(
+        setup.contract.try_create_pool(&setup.admin, &setup.pool_id, &setup.token_a, &token_c),
+        Err(Ok(LiquidityError::PoolAlreadyExists))
+    );
+    assert_eq!(
+        setup.contract.try_create_pool(&setup.admin, &String::from_str(env, ""), &setup.token_a, &token_c),
+        Err(Ok(LiquidityError::InvalidPoolId))
+    );
+    assert_eq!(
+        setup.contract.try_create_pool(&setup.admin, &String::from_str(env, "A_A"), &setup.token_a, &setup.token_a),
+        Err(Ok(LiquidityError::InvalidPoolId))
+    );
+
+    // Only the admin and the swap contract may open pools
+    let outsider = Address::generate(env);
+    let pool_bc = String::from_str(env, "B_C");
+    assert_eq!(
+        setup.contract.try_create_pool(&outsider, &pool_bc, &setup.token_b, &token_c),
+        Err(Ok(LiquidityError::UnauthorizedAccess))
+    );
+    let swap = Address::generate(env);
+    setup.contract.set_swap_contract(&setup.admin, &swap);
+    assert_eq!(
+        setup.contract.try_create_pool(&outsider, &pool_bc, &setup.token_b, &token_c),
+        Err(Ok(LiquidityError::UnauthorizedAccess))
+    );
+    setup.contract.create_pool(&swap, &pool_bc, &setup.token_b, &token_c);
+    assert_eq!(setup.contract.get_reserves(&pool_bc).token_b, token_c);
+}
+
+#[test]
+fn test_position_pnl_at_oracle_prices() {
+    let setup = Setup::new();
+    let swap_contract = Address::generate(&setup.env);
+    setup.contract.set_swap_contract(&setup.admin, &swap_contract);
+    let provider = setup.provider(DEPOSIT);
+    let deposit = setup.deposit(&provider, DEPOSIT);
+    let basis = deposit.lp_tokens;
+
+    // Without prices the position cannot be valued
+    assert_eq!(
+        setup.contract.try_get_position_pnl(&provider, &setup.pool_id),
+        Err(Ok(LiquidityError::PriceUnavailable))
+    );
+
+    let oracle = setup.set_prices(USD, USD);
+    let pnl = setup.contract.get_position_pnl(&provider, &setup.pool_id);
+    assert_eq!((pnl.impermanent_loss_usd, pnl.fee_income_usd), (0, 0));
+    assert_eq!((pnl.hold_value_usd, pnl.position_value_usd), (20 * basis, 20 * basis));
+
+    // Arbitrage takes A to a quarter of B: the pool doubles its A and halves its B
+    setup.mint(&setup.token_a, &setup.contract.address, DEPOSIT);
+    setup.contract.swap(&setup.pool_id, &setup.token_a, &DEPOSIT, &0, &(DEPOSIT / 2), &swap_contract);
+    oracle.set_price(&String::from_str(&setup.env, "A"), &(USD / 4));
+
+    // Held: basis * ($0.25 + $1); pooled: 2 * basis * $0.25 + basis / 2 * $1
+    let pnl = setup.contract.get_position_pnl(&provider, &setup.pool_id);
+    assert_eq!(pnl.hold_value_usd, basis * 25 / 2);
+    assert_eq!(pnl.position_value_usd, basis * 10);
+    assert_eq!(pnl.impermanent_loss_usd, basis * 5 / 2);
+
+    // Fees are income, not principal, so they leave the loss unchanged
+    let (amount_in, fee, amount_out) = (100_000_000, 300_000, 24_801_365);
+    setup.mint(&setup.token_a, &setup.contract.address, amount_in);
+    setup.contract.swap(&setup.pool_id, &setup.token_a, &amount_in, &fee, &amount_out, &swap_contract);
+    let pnl = setup.contract.get_position_pnl(&provider, &setup.pool_id);
+    assert_eq!((pnl.fee_income_a, pnl.fee_income_b), (fee * basis / DEPOSIT, 0));
+    assert_eq!(pnl.fee_income_usd, pnl.fee_income_a * 5 / 2);
+    assert!((pnl.impermanent_loss_usd - basis * 5 / 2).abs() <= basis / 1_000);
+
+    // Withdrawing half realizes half of the loss on the position
+    setup.contract.remove_liquidity(&provider, &setup.pool_id, &(basis / 2), &0, &0, &u64::MAX);
+    let position = setup.contract.get_position(&provider, &setup.pool_id);
+    let realized = pnl.impermanent_loss_usd / 2;
+    assert!((position.impermanent_loss - realized).abs() <= 10);
+    assert_eq!(position.fees_earned_a, pnl.fee_income_a / 2);
+    let after = setup.contract.get_position_pnl(&provider, &setup.pool_id);
+    assert!((after.impermanent_loss_usd - pnl.impermanent_loss_usd).abs() <= 10);
+    setup.assert_reconciled();
+}
+
+#[test]
+fn test_il_compensation_paid_from_fee_funded_pool() {
+    let setup = Setup::new();
+    let oracle = setup.set_prices(USD, USD);
+    setup.contract.set_protection_config(&setup.admin, &ProtectionConfig {
+        fee_share_bps: 5_000,
+        max_coverage_bps: BASIS_POINTS as u32,
+        vesting_period: 100,
+        max_payout_usd: 100 * USD,
+    });
+    let funder = setup.provider(DEPOSIT);
+    setup.contract.fund_protection(&funder, &setup.pool_id, &setup.token_a, &DEPOSIT);
+
+    let provider = setup.provider(DEPOSIT);
+    let lp_tokens = setup.deposit(&provider, DEPOSIT).lp_tokens;
+    setup.crash_token_a(&oracle);
+
+    // Half of the fee goes to the fund rather than the pool
+    let (amount_in, fee, amount_out) = (100_000_000, 300_000, 24_801_365);
+    setup.mint(&setup.token_a, &setup.contract.address, amount_in);
+    setup.contract.swap(&setup.pool_id, &setup.token_a, &amount_in, &fee, &amount_out, &setup.admin);
+    assert_eq!(setup.contract.get_protection_fund(&setup.pool_id).balance_a, DEPOSIT + fee / 2);
+    assert_eq!(setup.contract.get_pool(&setup.pool_id).reserve_a, 2 * DEPOSIT + amount_in - fee / 2);
+
+    // Half the position loses about $125, but fully vested coverage pays the $100 cap: 400 A at $0.25
+    setup.set_time(100);
+    let half = lp_tokens / 2;
+    let (amount_a, _) = setup.contract.remove_liquidity(&provider, &setup.pool_id, &half, &0, &0, &u64::MAX);
+    let compensation_a = 4_000_000_000;
+    assert_eq!(setup.balance(&setup.token_a, &provider), amount_a + compensation_a);
+    let position = setup.contract.get_position(&provider, &setup.pool_id);
+    assert_eq!(position.il_compensated, 100 * USD);
+    assert!(position.impermanent_loss > 100 * USD);
+
+    // The cap covers the position's lifetime, so withdrawing the rest pays nothing more
+    let (rest_a, _) = setup.contract.remove_liquidity(&provider, &setup.pool_id, &(lp_tokens - half), &0, &0, &u64::MAX);
+    assert_eq!(setup.balance(&setup.token_a, &provider), amount_a + rest_a + compensation_a);
+    assert_eq!(setup.contract.get_position(&provider, &setup.pool_id).il_compensated, 100 * USD);
+
+    let fund = setup.contract.get_protection_fund(&setup.pool_id);
+    assert_eq!(fund.balance_a, DEPOSIT + fee / 2 - compensation_a);
+    assert_eq!(fund.total_compensated_usd, 100 * USD);
+    setup.assert_reconciled();
+}
+
+#[test]
+fn test_il_compensation_vests_with_age() {
+    let setup = Setup::new();
+    let oracle = setup.set_prices(USD, USD);
+    setup.contract.set_protection_config(&setup.admin, &ProtectionConfig {
+        fee_share_bps: 0,
+        max_coverage_bps: BASIS_POINTS as u32,
+        vesting_period: 100,
+        max_payout_usd: 10_000 * USD,
+    });
+    let funder = setup.provider(DEPOSIT);
+    setup.contract.fund_protection(&funder, &setup.pool_id, &setup.token_a, &DEPOSIT);
+
+    let provider = setup.provider(DEPOSIT);
+    let lp_tokens = setup.deposit(&provider, DEPOSIT).lp_tokens;
+    setup.crash_token_a(&oracle);
+
+    // A quarter of the way through vesting, a quarter of the loss is covered
+    setup.set_time(25);
+    setup.contract.remove_liquidity(&provider, &setup.pool_id, &lp_tokens, &0, &0, &u64::MAX);
+    let position = setup.contract.get_position(&provider, &setup.pool_id);
+    assert_eq!(position.impermanent_loss, lp_tokens * 5 / 2);
+    // Paid in base units of A, each worth 2.5 at $0.25
+    let shortfall = position.impermanent_loss / 4 - position.il_compensated;
+    assert!((0..3).contains(&shortfall));
+}
+
+#[test]
+fn test_rewards_emitted_pro_rata_until_reserve_spent() {
+    let setup = Setup::new();
+    let first = setup.provider(DEPOSIT);
+    let second = setup.provider(DEPOSIT);
+    let reward_token = setup.start_emissions(10, 3_000);
+
+    setup.deposit_staked(&first, DEPOSIT);
+    setup.set_time(100);
+    assert!((setup.contract.get_pending_rewards(&first, &setup.pool_id) - 1_000).abs() <= 1);
+
+    // A second provider of the same size shares emissions from its deposit on
+    setup.deposit_staked(&second, DEPOSIT);
+    setup.set_time(200);
+    assert!((setup.contract.get_pending_rewards(&first, &setup.pool_id) - 1_500).abs() <= 1);
+    assert!((setup.contract.get_pending_rewards(&second, &setup.pool_id) - 500).abs() <= 1);
+
+    let payout = setup.contract.claim_rewards(&first, &setup.pool_id);
+    assert_eq!(payout.reward_token, reward_token);
+    assert_eq!(setup.balance(&reward_token, &first), payout.amount);
+    assert_eq!(setup.contract.get_pending_rewards(&first, &setup.pool_id), 0);
+
+    // Emissions stop once the funded reserve is spent
+    setup.set_time(1_000);
+    let paid = payout.amount
+        + setup.contract.claim_rewards(&first, &setup.pool_id).amount
+        + setup.contract.claim_rewards(&second, &setup.pool_id).amount;
+    assert!((2_998..=3_000).contains(&paid));
+    let rewards = setup.contract.get_pool_rewards(&setup.pool_id);
+    assert_eq!(rewards.reward_reserve, 0);
+    assert_eq!(setup.contract.get_pool(&setup.pool_id).total_rewards_distributed, paid);
+}
+
+#[test]
+fn test_campaign_token_whitelist() {
+    let setup = Setup::new();
+    let creator = Address::generate(&setup.env);
+    let reward_token = setup.env.register_stellar_asset_contract_v2(setup.admin.clone()).address();
+    setup.mint(&reward_token, &creator, 10_000);
+
+    assert_eq!(
+        setup.contract.try_create_campaign(&creator, &setup.pool_id, &reward_token, &1_000, &0, &100),
+        Err(Ok(LiquidityError::RewardTokenNotAllowed))
+    );
+    setup.contract.set_campaign_token(&setup.admin, &reward_token, &1_000);
+    assert_eq!(
+        setup.contract.try_create_campaign(&creator, &setup.pool_id, &reward_token, &999, &0, &100),
+        Err(Ok(LiquidityError::InvalidAmount))
+    );
+
+    let id = setup.contract.create_campaign(&creator, &setup.pool_id, &reward_token, &1_000, &0, &100);
+    assert_eq!(setup.contract.get_pool_campaigns(&setup.pool_id), Vec::from_array(&setup.env, [id]));
+    assert_eq!(setup.balance(&reward_token, &creator), 9_000);
+}
+
+#[test]
+fn test_overlapping_campaigns_accrue_separately() {
+    let setup = Setup::new();
+    let first = setup.provider(DEPOSIT);
+    let second = setup.provider(DEPOSIT);
+    let (_, token_1, id_1) = setup.start_campaign(1_000, 0, 100);
+    let (_, token_2, id_2) = setup.start_campaign(1_000, 50, 150);
+    assert_eq!(setup.contract.get_pool_campaigns(&setup.pool_id), Vec::from_array(&setup.env, [id_1, id_2]));
+
+    // The first provider has the first half of campaign 1 alone, then splits everything evenly
+    setup.deposit_staked(&first, DEPOSIT);
+    setup.set_time(50);
+    setup.deposit_staked(&second, DEPOSIT);
+    setup.set_time(150);
+
+    let payouts = setup.contract.claim_all(&first, &setup.pool_id);
+    assert_eq!(payouts.len(), 2);
+    let (payout_1, payout_2) = (payouts.get_unchecked(0), payouts.get_unchecked(1));
+    assert_eq!((payout_1.reward_token, payout_2.reward_token), (token_1.clone(), token_2.clone()));
+    assert!((payout_1.amount - 750).abs() <= 1);
+    assert!((payout_2.amount - 500).abs() <= 1);
+
+    let payouts = setup.contract.claim_all(&second, &setup.pool_id);
+    assert!((payouts.get_unchecked(0).amount - 250).abs() <= 1);
+    assert!((payouts.get_unchecked(1).amount - 500).abs() <= 1);
+    assert!((setup.balance(&token_1, &first) + setup.balance(&token_1, &second) - 1_000).abs() <= 1);
+
+    // Claims are recorded per campaign start
+    let distribution = setup.contract.get_distribution(&setup.pool_id, &0);
+    assert_eq!(distribution.participants, 2);
+    assert_eq!(distribution.total_rewards, setup.balance(&token_1, &first) + setup.balance(&token_1, &second));
+    assert_eq!(setup.contract.get_campaign(&id_2).emitted, 1_000);
+
+    // Nothing more accrues after both campaigns end
+    setup.set_time(300);
+    assert_eq!(setup.contract.claim_all(&first, &setup.pool_id).len(), 0);
+}
+
+#[test]
+fn test_unemitted_campaign_rewards_return_to_creator() {
+    let setup = Setup::new();
+    let provider = setup.provider(DEPOSIT);
+    let (creator, reward_token, id) = setup.start_campaign(1_000, 0, 100);
+
+    // Nothing is staked for the first 40 seconds, so that part is never emitted
+    setup.set_time(40);
+    setup.deposit_staked(&provider, DEPOSIT);
+    setup.set_time(50);
+    assert_eq!(
+        setup.contract.try_withdraw_unemitted(&creator, &id),
+        Err(Ok(LiquidityError::DistributionFailed))
+    );
+    setup.set_time(100);
+    assert_eq!(
+        setup.contract.try_withdraw_unemitted(&provider, &id),
+        Err(Ok(LiquidityError::UnauthorizedAccess))
+    );
+
+    assert_eq!(setup.contract.withdraw_unemitted(&creator, &id), 400);
+    assert_eq!(setup.balance(&reward_token, &creator), 400);
+    assert_eq!(setup.contract.withdraw_unemitted(&creator, &id), 0);
+    let campaign = setup.contract.get_campaign(&id);
+    assert_eq!((campaign.emitted, campaign.total_amount), (600, 600));
+
+    // Stakers keep what was emitted to them
+    let payouts = setup.contract.claim_all(&provider, &setup.pool_id);
+    assert!((payouts.get_unchecked(0).amount - 600).abs() <= 1);
+    let reconciliation = setup.contract.reconcile_reserves(&reward_token);
+    assert_eq!(reconciliation.tracked_reserves, reconciliation.actual_balance);
+}
+
+#[test]
+fn test_rewards_accrue_to_staked_shares_only() {
+    let setup = Setup::new();
+    let staker = setup.provider(DEPOSIT);
+    let seller = setup.provider(DEPOSIT);
+    let buyer = Address::generate(&setup.env);
+    let reward_token = setup.start_emissions(10, 3_000);
+
+    let deposit = setup.deposit(&staker, DEPOSIT);
+    setup.contract.stake(&staker, &setup.pool_id, &deposit.lp_tokens);
+    assert_eq!(setup.lp_token.balance(&staker), 0);
+    assert_eq!(setup.contract.get_staked_shares(&staker, &setup.pool_id), deposit.lp_tokens);
+
+    // Unstaked shares earn nothing, wherever they are transferred
+    setup.set_time(100);
+    let sold = setup.deposit(&seller, DEPOSIT).lp_tokens;
+    setup.set_time(200);
+    setup.lp_token.transfer(&seller, &buyer, &sold);
+    assert!((setup.contract.get_pending_rewards(&staker, &setup.pool_id) - 2_000).abs() <= 1);
+    assert_eq!(setup.contract.get_pending_rewards(&seller, &setup.pool_id), 0);
+
+    // Staked, the transferred shares share emissions with the first staker
+    setup.contract.stake(&buyer, &setup.pool_id, &sold);
+    setup.set_time(300);
+    let staker_rewards = setup.contract.get_pending_rewards(&staker, &setup.pool_id);
+    let buyer_rewards = setup.contract.get_pending_rewards(&buyer, &setup.pool_id);
+    assert!((staker_rewards - 2_500).abs() <= 1);
+    assert!((buyer_rewards - 500).abs() <= 1);
+
+    // Emissions stop once the funded reserve is spent
+    setup.set_time(1_000);
+    let paid = setup.contract.claim_rewards(&staker, &setup.pool_id).amount
+        + setup.contract.claim_rewards(&buyer, &setup.pool_id).amount;
+    assert!((2_998..=3_000).contains(&paid));
+    assert_eq!(setup.balance(&reward_token, &staker) + setup.balance(&reward_token, &buyer), paid);
+
+    // Unstaking returns the shares and stops accrual
+    setup.contract.unstake(&buyer, &setup.pool_id, &sold);
+    assert_eq!(setup.lp_token.balance(&buyer), sold);
+    assert_eq!(setup.contract.get_pool_rewards(&setup.pool_id).total_staked, deposit.lp_tokens);
+}
+
+#[test]
+fn test_transfer_then_lock() {
+    let setup = Setup::new();
+    let provider = setup.provider(DEPOSIT);
+    let receiver = Address::generate(&setup.env);
+    let lp_tokens = setup.deposit(&provider, DEPOSIT).lp_tokens;
+
+    // Shares transferred away can no longer back the provider's lock
+    setup.lp_token.transfer(&provider, &receiver, &lp_tokens);
+    assert_eq!(
+        setup.contract.try_lock_position(&provider, &setup.pool_id, &lp_tokens, &3),
+        Err(Ok(LiquidityError::InsufficientLiquidity))
+    );
+
+    // The receiver locks them instead, and the contract holds them
+    setup.contract.lock_position(&receiver, &setup.pool_id, &lp_tokens, &3);
+    assert_eq!(setup.lp_token.balance(&receiver), 0);
+    assert_eq!(setup.lp_token.balance(&setup.contract.address), lp_tokens + MINIMUM_LIQUIDITY);
+    assert_eq!(setup.contract.get_staked_shares(&receiver, &setup.pool_id), lp_tokens);
+    assert_eq!(
+        setup.contract.get_pool_rewards(&setup.pool_id).total_staked,
+        lp_tokens * 12_500 / BASIS_POINTS
+    );
+    assert_eq!(
+        setup.contract.try_unstake(&receiver, &setup.pool_id, &lp_tokens),
+        Err(Ok(LiquidityError::InsufficientLiquidity))
+    );
+}
+
+#[test]
+fn test_lock_takes_staked_shares_first() {
+    let setup = Setup::new();
+    let provider = setup.provider(DEPOSIT);
+    let receiver = Address::generate(&setup.env);
+    let lp_tokens = setup.deposit(&provider, DEPOSIT).lp_tokens;
+    let half = lp_tokens / 2;
+
+    setup.contract.stake(&provider, &setup.pool_id, &half);
+    setup.lp_token.transfer(&provider, &receiver, &(lp_tokens - half));
+
+    // Only the staked half is left to lock
+    assert_eq!(
+        setup.contract.try_lock_position(&provider, &setup.pool_id, &lp_tokens, &1),
+        Err(Ok(LiquidityError::InsufficientLiquidity))
+    );
+    setup.contract.lock_position(&provider, &setup.pool_id, &half, &1);
+    let position = setup.contract.get_position(&provider, &setup.pool_id);
+    assert_eq!((position.reward_stake, position.locked_shares), (half, half));
+    assert_eq!(position.reward_weight, half * 11_000 / BASIS_POINTS);
+}
+
+#[test]
+fn test_expired_campaign_remainder_returns_to_creator() {
+    let setup = Setup::new();
+    let provider = setup.provider(DEPOSIT);
+    let (creator, reward_token, id) = setup.start_campaign(1_000, 0, 100);
+
+    // Nothing is staked for the first 40 seconds, and the provider only claims up to 60
+    setup.set_time(40);
+    setup.deposit_staked(&provider, DEPOSIT);
+    setup.set_time(60);
+    let claimed = setup.contract.claim_all(&provider, &setup.pool_id).get_unchecked(0).amount;
+    assert!((claimed - 200).abs() <= 1);
+
+    // A new campaign after the claim period drops the old one and returns what it still holds
+    let now = 100 + CAMPAIGN_CLAIM_PERIOD + 1;
+    setup.set_time(now);
+    let (_, _, next) = setup.start_campaign(1_000, now, now + 100);
+    assert_eq!(setup.contract.get_pool_campaigns(&setup.pool_id), Vec::from_array(&setup.env, [next]));
+    assert_eq!(setup.balance(&reward_token, &creator), 1_000 - claimed);
+    assert_eq!(setup.contract.get_campaign(&id).total_amount, claimed);
+    let reconciliation = setup.contract.reconcile_reserves(&reward_token);
+    assert_eq!((reconciliation.tracked_reserves, reconciliation.actual_balance), (0, 0));
+}
+
+#[test]
+fn test_locked_shares_earn_boosted_rewards() {
+    let setup = Setup::new();
+    let locker = setup.provider(DEPOSIT);
+    let holder = setup.provider(DEPOSIT);
+    setup.start_emissions(30, 3_000);
+
+    let lp_tokens = setup.deposit(&locker, DEPOSIT).lp_tokens;
+    let staked = setup.deposit_staked(&holder, DEPOSIT);
+    let (_, duration, multiplier) = LOCK_TIERS[3];
+    assert_eq!(setup.contract.lock_position(&locker, &setup.pool_id, &lp_tokens, &12), duration);
+    assert_eq!(setup.lp_token.balance(&locker), 0);
+    assert_eq!(setup.lp_token.balance(&setup.contract.address), lp_tokens + MINIMUM_LIQUIDITY + staked);
+
+    // Twelve months doubles the locked shares' weight
+    let position = setup.contract.get_position(&locker, &setup.pool_id);
+    assert_eq!(position.reward_weight, lp_tokens * multiplier as i128 / BASIS_POINTS);
+    setup.set_time(100);
+    assert!((setup.contract.get_pending_rewards(&locker, &setup.pool_id) - 2_000).abs() <= 1);
+    assert!((setup.contract.get_pending_rewards(&holder, &setup.pool_id) - 1_000).abs() <= 1);
+}
+
+#[test]
+fn test_early_unlock_penalty() {
+    let setup = Setup::new();
+    let provider = setup.provider(DEPOSIT);
+    let lp_tokens = setup.deposit(&provider, DEPOSIT).lp_tokens;
+    let (_, duration, _) = LOCK_TIERS[0];
+
+    let lock_until = setup.contract.lock_position(&provider, &setup.pool_id, &lp_tokens, &1);
+    assert_eq!(lock_until, duration);
+    assert_eq!(
+        setup.contract.try_lock_position(&provider, &setup.pool_id, &1, &12),
+        Err(Ok(LiquidityError::InsufficientLiquidity))
+    );
+    assert_eq!(
+        setup.contract.try_kick_lock(&provider, &setup.pool_id),
+        Err(Ok(LiquidityError::PositionLocked))
+    );
+
+    // Halfway through, half of the maximum penalty is burned
+    setup.set_time(duration / 2);
+    let returned = setup.contract.unlock_position(&provider, &setup.pool_id);
+    let penalty = lp_tokens * MAX_EARLY_EXIT_PENALTY / BASIS_POINTS / 2;
+    assert_eq!(returned, lp_tokens - penalty);
+    assert_eq!(setup.lp_token.balance(&provider), returned);
+
+    let pool = setup.contract.get_pool(&setup.pool_id);
+    assert_eq!(pool.total_lp_tokens, DEPOSIT - penalty);
+    assert_eq!(setup.lp_token.total_supply(), pool.total_lp_tokens);
+    assert_eq!(pool.reserve_a, DEPOSIT);
+    let position = setup.contract.get_position(&provider, &setup.pool_id);
+    assert_eq!((position.lp_tokens, position.reward_stake, position.locked_shares), (returned, 0, 0));
+    assert_eq!(setup.contract.get_pool_rewards(&setup.pool_id).total_staked, 0);
+}
+
+#[test]
+fn test_expired_lock_unlocks_without_penalty() {
+    let setup = Setup::new();
+    let provider = setup.provider(DEPOSIT);
+    let lp_tokens = setup.deposit(&provider, DEPOSIT).lp_tokens;
+    let (_, duration, multiplier) = LOCK_TIERS[1];
+
+    assert_eq!(
+        setup.contract.try_lock_position(&provider, &setup.pool_id, &lp_tokens, &2),
+        Err(Ok(LiquidityError::InvalidLockPeriod))
+    );
+    setup.contract.lock_position(&provider, &setup.pool_id, &lp_tokens, &3);
+    assert_eq!(
+        setup.contract.get_pool_rewards(&setup.pool_id).total_staked,
+        lp_tokens * multiplier as i128 / BASIS_POINTS
+    );
+
+    // Once expired the boost can be dropped by anyone
+    setup.set_time(duration);
+    setup.contract.kick_lock(&provider, &setup.pool_id);
+    assert_eq!(setup.contract.get_pool_rewards(&setup.pool_id).total_staked, lp_tokens);
+
+    assert_eq!(setup.contract.unlock_position(&provider, &setup.pool_id), lp_tokens);
+    assert_eq!(setup.lp_token.balance(&provider), lp_tokens);
+    assert_eq!(setup.contract.get_pool(&setup.pool_id).total_lp_tokens, DEPOSIT);
+}
+
+#[test]
+fn test_fee_history_keeps_trailing_week() {
+    let setup = Setup::new();
+    let swap_contract = Address::generate(&setup.env);
+    setup.contract.set_swap_contract(&setup.admin, &swap_contract);
+    let provider = setup.provider(DEPOSIT);
+    setup.deposit(&provider, DEPOSIT);
+
+    // One swap a day for ten days, so the ring buffer wraps past its seven slots
+    let amount_in = 1_000_000_000;
+    let swap = |fee: i128| {
+        setup.mint(&setup.token_a, &setup.contract.address, amount_in);
+        setup.contract.swap(&setup.pool_id, &setup.token_a, &amount_in, &fee, &1, &swap_contract);
+    };
+    for day in 0..10 {
+        setup.set_time(day * SECONDS_PER_DAY + 1);
+        swap(3_000_000 + day as i128);
+    }
+    swap(3_000_009);
+
+    let history = setup.contract.get_fee_history(&setup.pool_id);
+    assert_eq!(history.len(), FEE_SNAPSHOT_DAYS as u32);
+    for (index, snapshot) in history.iter().enumerate() {
+        let day = 3 + index as u64;
+        let swaps = if day == 9 { 2 } else { 1 };
+        assert_eq!(snapshot.day, day);
+        assert_eq!((snapshot.fees_a, snapshot.fees_b), (swaps * (3_000_000 + day as i128), 0));
+        assert_eq!((snapshot.volume_a, snapshot.volume_b), (swaps * amount_in, 0));
+    }
+
+    // Trailing fees over the elapsed part of the window, annualized over TVL
+    let fees: i128 = history.iter().map(|snapshot| snapshot.fees_a).sum();
+    let window = (FEE_SNAPSHOT_DAYS - 1) * SECONDS_PER_DAY + 1;
+    let pool = setup.contract.get_pool(&setup.pool_id);
+    let expected = fees * SECONDS_PER_YEAR / window as i128 * BASIS_POINTS / (2 * pool.reserve_a);
+    assert!(expected > 0);
+    assert_eq!(setup.contract.refresh_apy(&setup.pool_id), expected as u32);
+    assert_eq!(setup.contract.get_pool(&setup.pool_id).current_apy, expected as u32);
+
+    // Days without swaps drop out as the window moves on
+    setup.set_time(12 * SECONDS_PER_DAY);
+    let history = setup.contract.get_fee_history(&setup.pool_id);
+    assert_eq!(history.len(), 4);
+    assert_eq!(history.get_unchecked(0).day, 6);
+}
+
+#[test]
+fn test_apy_includes_priced_emissions() {
+    let setup = Setup::new();
+    let provider = setup.provider(DEPOSIT);
+    setup.deposit_staked(&provider, DEPOSIT);
+    let reward_token = setup.start_emissions(10, 1_000_000);
+
+    // The reward token has no price yet, so only fees count
+    let oracle = setup.set_prices(USD, USD);
+    assert_eq!(setup.contract.refresh_apy(&setup.pool_id), 0);
+
+    // 10 base units a second of a $1 token is $31.536 a year against $2,000 of liquidity
+    let asset = String::from_str(&setup.env, "R");
+    oracle.set_price(&asset, &USD);
+    setup.contract.set_price_feed(&setup.admin, &reward_token, &asset);
+    assert_eq!(setup.contract.refresh_apy(&setup.pool_id), 157);
+
+    // An empty reward reserve emits nothing
+    setup.set_time(100_000);
+    assert_eq!(setup.contract.refresh_apy(&setup.pool_id), 0);
+}
+
+#[test]
+fn test_global_stats_at_oracle_prices() {
+    let setup = Setup::new();
+    let oracle = setup.set_prices(USD, USD);
+    let token_c = setup.env.register_stellar_asset_contract_v2(setup.admin.clone()).address();
+    let asset_c = String::from_str(&setup.env, "C");
+    oracle.set_price(&asset_c, &(2 * USD));
+    setup.contract.set_price_feed(&setup.admin, &token_c, &asset_c);
+    let pool_c = String::from_str(&setup.env, "A_C");
+    setup.contract.create_pool(&setup.admin, &pool_c, &setup.token_a, &token_c);
+    assert_eq!(setup.contract.get_global_stats().total_pools, 2);
+
+    // A provider counts once however many deposits and pools they hold
+    let provider = setup.provider(3 * DEPOSIT);
+    setup.deposit(&provider, DEPOSIT);
+    setup.deposit(&provider, DEPOSIT);
+    setup.mint(&token_c, &provider, DEPOSIT);
+    setup.contract.add_liquidity(&provider, &pool_c, &DEPOSIT, &DEPOSIT, &0, &0, &u64::MAX);
+    assert_eq!(setup.contract.get_global_stats().active_providers, 1);
+
+    let other = setup.provider(DEPOSIT);
+    let deposit = setup.deposit(&other, DEPOSIT);
+    assert_eq!(setup.contract.get_global_stats().active_providers, 2);
+
+    // $1 tokens in the first pool, a $1 and a $2 token in the second
+    assert_eq!(setup.contract.get_global_stats().total_tvl_usd, 60 * DEPOSIT + 30 * DEPOSIT);
+
+    setup.contract.remove_liquidity(&other, &setup.pool_id, &deposit.lp_tokens, &0, &0, &u64::MAX);
+    let stats = setup.contract.get_global_stats();
+    assert_eq!(stats.active_providers, 1);
+    assert_eq!(stats.total_tvl_usd, 40 * DEPOSIT + 30 * DEPOSIT);
+
+    // Emissions give the first pool an APY; the average weighs it by TVL against the fee-less second pool
+    let reward_token = setup.start_emissions(10, 1_000_000);
+    let asset_r = String::from_str(&setup.env, "R");
+    oracle.set_price(&asset_r, &USD);
+    setup.contract.set_price_feed(&setup.admin, &reward_token, &asset_r);
+    let apy = setup.contract.refresh_apy(&setup.pool_id);
+    assert!(apy > 0);
+    assert_eq!(setup.contract.refresh_apy(&pool_c), 0);
+    assert_eq!(setup.contract.get_global_stats().average_apy, apy * 40 / 70);
+
+    // A price move is picked up when the pool is refreshed
+    oracle.set_price(&asset_c, &(4 * USD));
+    setup.contract.refresh_apy(&pool_c);
+    let stats = setup.contract.get_global_stats();
+    assert_eq!(stats.total_tvl_usd, 40 * DEPOSIT + 50 * DEPOSIT);
+    assert_eq!(stats.average_apy, apy * 40 / 90);
+}
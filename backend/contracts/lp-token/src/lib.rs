@@ -0,0 +1,316 @@
+#![no_std]
+
+//! # SWAVE LP Token Contract
+//!
+//! SEP-41 token representing a share of one SWAVE liquidity pool. The liquidity
+//! contract deploys one instance per pool, mints shares on `add_liquidity` and
+//! burns them on `remove_liquidity`. Shares are freely transferable, so LP
+//! positions show up in wallets and can be posted as collateral.
+//!
+//! ## Features
+//! - Standard SEP-41 interface (`balance`, `transfer`, `approve`, `transfer_from`, `burn`)
+//! - Minting and burning restricted to the owning liquidity contract
+//! - Expiring allowances
+
+use soroban_sdk::{
+    contract, contractimpl, contracttype, contracterror, contractmeta,
+    panic_with_error, symbol_short, token::TokenInterface, Address, Env, String,
+};
+
+// Contract metadata
+contractmeta!(
+    key = "Description",
+    val = "SWAVE LP Token - Transferable Liquidity Pool Shares"
+);
+
+contractmeta!(key = "Version", val = "1.0.0");
+
+/// Token constants
+const LP_DECIMALS: u32 = 7;                 // Same precision as pool assets
+const BALANCE_TTL: u32 = 518_400;           // ~30 days in ledgers
+const BALANCE_TTL_THRESHOLD: u32 = 259_200; // Extend when under ~15 days
+
+/// Contract errors
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum LpTokenError {
+    AlreadyInitialized = 1,
+    NotInitialized = 2,
+    InvalidAmount = 3,
+    InsufficientBalance = 4,
+    InsufficientAllowance = 5,
+    InvalidExpiration = 6,
+}
+
+/// Allowance granted by an owner to a spender
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AllowanceValue {
+    /// Approved amount
+    pub amount: i128,
+    /// Ledger after which the allowance is void
+    pub expiration_ledger: u32,
+}
+
+/// Token metadata
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TokenMetadata {
+    /// Pool this token represents
+    pub pool_id: String,
+    /// Token name
+    pub name: String,
+    /// Token symbol
+    pub symbol: String,
+    /// Decimal places
+    pub decimals: u32,
+}
+
+/// Storage keys
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    /// Liquidity contract allowed to mint and burn
+    Admin,
+    /// Token metadata
+    Metadata,
+    /// Total shares outstanding
+    TotalSupply,
+    /// Holder balance
+    Balance(Address),
+    /// Spender allowance
+    Allowance(Address, Address), // (owner, spender)
+}
+
+/// SWAVE LP Token Contract
+#[contract]
+pub struct LpTokenContract;
+
+#[contractimpl]
+impl LpTokenContract {
+    
+    /// Initialize the share token for a pool (called once by the liquidity contract)
+    pub fn initialize(
+        env: Env,
+        admin: Address,
+        pool_id: String,
+    ) -> Result<(), LpTokenError> {
+        if env.storage().instance().has(&DataKey::Admin) {
+            return Err(LpTokenError::AlreadyInitialized);
+        }
+        
+        let metadata = TokenMetadata {
+            pool_id,
+            name: String::from_str(&env, "SWAVE Liquidity Share"),
+            symbol: String::from_str(&env, "SWAVE-LP"),
+            decimals: LP_DECIMALS,
+        };
+        
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage().instance().set(&DataKey::Metadata, &metadata);
+        env.storage().instance().set(&DataKey::TotalSupply, &0i128);
+        
+        Ok(())
+    }
+
+    /// Mint shares to a provider (liquidity contract only)
+    pub fn mint(env: Env, to: Address, amount: i128) -> Result<(), LpTokenError> {
+        let admin = Self::get_admin(&env)?;
+        admin.require_auth();
+        
+        if amount <= 0 {
+            return Err(LpTokenError::InvalidAmount);
+        }
+        
+        Self::write_balance(&env, &to, Self::read_balance(&env, &to) + amount);
+        let supply = Self::total_supply(env.clone());
+        env.storage().instance().set(&DataKey::TotalSupply, &(supply + amount));
+        
+        env.events().publish((symbol_short!("mint"), admin, to), amount);
+        Ok(())
+    }
+
+    /// Total shares outstanding
+    pub fn total_supply(env: Env) -> i128 {
+        env.storage().instance().get(&DataKey::TotalSupply).unwrap_or(0)
+    }
+
+    /// Pool this token represents
+    pub fn pool_id(env: Env) -> Result<String, LpTokenError> {
+        Ok(Self::get_metadata(&env)?.pool_id)
+    }
+
+    /// Liquidity contract that owns this token
+    pub fn admin(env: Env) -> Result<Address, LpTokenError> {
+        Self::get_admin(&env)
+    }
+
+    // Helper functions
+
+    fn get_admin(env: &Env) -> Result<Address, LpTokenError> {
+        env.storage().instance()
+            .get(&DataKey::Admin)
+            .ok_or(LpTokenError::NotInitialized)
+    }
+
+    fn get_metadata(env: &Env) -> Result<TokenMetadata, LpTokenError> {
+        env.storage().instance()
+            .get(&DataKey::Metadata)
+            .ok_or(LpTokenError::NotInitialized)
+    }
+
+    fn read_balance(env: &Env, holder: &Address) -> i128 {
+        let key = DataKey::Balance(holder.clone());
+        match env.storage().persistent().get::<DataKey, i128>(&key) {
+            Some(balance) => {
+                env.storage().persistent().extend_ttl(&key, BALANCE_TTL_THRESHOLD, BALANCE_TTL);
+                balance
+            }
+            None => 0,
+        }
+    }
+
+    fn write_balance(env: &Env, holder: &Address, amount: i128) {
+        let key = DataKey::Balance(holder.clone());
+        env.storage().persistent().set(&key, &amount);
+        env.storage().persistent().extend_ttl(&key, BALANCE_TTL_THRESHOLD, BALANCE_TTL);
+    }
+
+    fn spend_balance(env: &Env, holder: &Address, amount: i128) {
+        if amount < 0 {
+            panic_with_error!(env, LpTokenError::InvalidAmount);
+        }
+        let balance = Self::read_balance(env, holder);
+        if balance < amount {
+            panic_with_error!(env, LpTokenError::InsufficientBalance);
+        }
+        Self::write_balance(env, holder, balance - amount);
+    }
+
+    fn read_allowance(env: &Env, from: &Address, spender: &Address) -> AllowanceValue {
+        let key = DataKey::Allowance(from.clone(), spender.clone());
+        match env.storage().temporary().get::<DataKey, AllowanceValue>(&key) {
+            Some(allowance) if allowance.expiration_ledger >= env.ledger().sequence() => allowance,
+            _ => AllowanceValue { amount: 0, expiration_ledger: 0 },
+        }
+    }
+
+    fn spend_allowance(env: &Env, from: &Address, spender: &Address, amount: i128) {
+        let allowance = Self::read_allowance(env, from, spender);
+        if allowance.amount < amount {
+            panic_with_error!(env, LpTokenError::InsufficientAllowance);
+        }
+        if amount > 0 {
+            let key = DataKey::Allowance(from.clone(), spender.clone());
+            env.storage().temporary().set(&key, &AllowanceValue {
+                amount: allowance.amount - amount,
+                expiration_ledger: allowance.expiration_ledger,
+            });
+        }
+    }
+
+    fn burn_shares(env: &Env, from: Address, amount: i128) {
+        // Burns only happen through the liquidity contract, keeping supply equal to the pool's share count
+        match Self::get_admin(env) {
+            Ok(admin) => admin.require_auth(),
+            Err(err) => panic_with_error!(env, err),
+        }
+        
+        Self::spend_balance(env, &from, amount);
+        let supply = Self::total_supply(env.clone());
+        env.storage().instance().set(&DataKey::TotalSupply, &(supply - amount));
+        
+        env.events().publish((symbol_short!("burn"), from), amount);
+    }
+}
+
+#[contractimpl]
+impl TokenInterface for LpTokenContract {
+    
+    fn allowance(env: Env, from: Address, spender: Address) -> i128 {
+        Self::read_allowance(&env, &from, &spender).amount
+    }
+
+    fn approve(env: Env, from: Address, spender: Address, amount: i128, expiration_ledger: u32) {
+        from.require_auth();
+        
+        if amount < 0 {
+            panic_with_error!(&env, LpTokenError::InvalidAmount);
+        }
+        if amount > 0 && expiration_ledger < env.ledger().sequence() {
+            panic_with_error!(&env, LpTokenError::InvalidExpiration);
+        }
+        
+        let key = DataKey::Allowance(from.clone(), spender.clone());
+        env.storage().temporary().set(&key, &AllowanceValue { amount, expiration_ledger });
+        if amount > 0 {
+            let live_for = expiration_ledger - env.ledger().sequence();
+            env.storage().temporary().extend_ttl(&key, live_for, live_for);
+        }
+        
+        env.events().publish(
+            (symbol_short!("approve"), from, spender),
+            (amount, expiration_ledger),
+        );
+    }
+
+    fn balance(env: Env, id: Address) -> i128 {
+        Self::read_balance(&env, &id)
+    }
+
+    fn transfer(env: Env, from: Address, to: Address, amount: i128) {
+        from.require_auth();
+        
+        Self::spend_balance(&env, &from, amount);
+        Self::write_balance(&env, &to, Self::read_balance(&env, &to) + amount);
+        
+        env.events().publish((symbol_short!("transfer"), from, to), amount);
+    }
+
+    fn transfer_from(env: Env, spender: Address, from: Address, to: Address, amount: i128) {
+        spender.require_auth();
+        
+        Self::spend_allowance(&env, &from, &spender, amount);
+        Self::spend_balance(&env, &from, amount);
+        Self::write_balance(&env, &to, Self::read_balance(&env, &to) + amount);
+        
+        env.events().publish((symbol_short!("transfer"), from, to), amount);
+    }
+
+    fn burn(env: Env, from: Address, amount: i128) {
+        from.require_auth();
+        
+        Self::burn_shares(&env, from, amount);
+    }
+
+    fn burn_from(env: Env, spender: Address, from: Address, amount: i128) {
+        spender.require_auth();
+        
+        Self::spend_allowance(&env, &from, &spender, amount);
+        Self::burn_shares(&env, from, amount);
+    }
+
+    fn decimals(env: Env) -> u32 {
+        Self::get_metadata(&env)
+            .map(|metadata| metadata.decimals)
+            .unwrap_or(LP_DECIMALS)
+    }
+
+    fn name(env: Env) -> String {
+        match Self::get_metadata(&env) {
+            Ok(metadata) => metadata.name,
+            Err(err) => panic_with_error!(&env, err),
+        }
+    }
+
+    fn symbol(env: Env) -> String {
+        match Self::get_metadata(&env) {
+            Ok(metadata) => metadata.symbol,
+            Err(err) => panic_with_error!(&env, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,102 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Ledger, MockAuth, MockAuthInvoke};
+use soroban_sdk::IntoVal;
+
+fn setup(env: &Env) -> (Address, LpTokenContractClient<'_>) {
+    env.mock_all_auths();
+    let admin = Address::generate(env);
+    let token = LpTokenContractClient::new(env, &env.register(LpTokenContract, ()));
+    token.initialize(&admin, &String::from_str(env, "A_B"));
+    (admin, token)
+}
+
+#[test]
+fn test_mint_transfer_and_burn() {
+    let env = Env::default();
+    let (admin, token) = setup(&env);
+    let holder = Address::generate(&env);
+    let receiver = Address::generate(&env);
+
+    assert_eq!(
+        token.try_initialize(&admin, &String::from_str(&env, "A_B")),
+        Err(Ok(LpTokenError::AlreadyInitialized))
+    );
+    assert_eq!(token.try_mint(&holder, &0), Err(Ok(LpTokenError::InvalidAmount)));
+
+    token.mint(&holder, &1_000);
+    token.transfer(&holder, &receiver, &400);
+    token.burn(&receiver, &100);
+    assert_eq!((token.balance(&holder), token.balance(&receiver)), (600, 300));
+    assert_eq!(token.total_supply(), 900);
+    assert_eq!(token.admin(), admin);
+    assert_eq!(token.decimals(), LP_DECIMALS);
+    assert!(token.try_transfer(&holder, &receiver, &601).is_err());
+}
+
+#[test]
+fn test_allowances_expire() {
+    let env = Env::default();
+    let (_, token) = setup(&env);
+    let owner = Address::generate(&env);
+    let spender = Address::generate(&env);
+    let receiver = Address::generate(&env);
+    token.mint(&owner, &1_000);
+
+    token.approve(&owner, &spender, &500, &100);
+    token.transfer_from(&spender, &owner, &receiver, &200);
+    token.burn_from(&spender, &owner, &100);
+    assert_eq!(token.allowance(&owner, &spender), 200);
+    assert!(token.try_transfer_from(&spender, &owner, &receiver, &201).is_err());
+    assert_eq!((token.balance(&owner), token.total_supply()), (700, 900));
+
+    env.ledger().with_mut(|ledger| ledger.sequence_number = 101);
+    assert_eq!(token.allowance(&owner, &spender), 0);
+    assert!(token.try_transfer_from(&spender, &owner, &receiver, &1).is_err());
+}
+
+#[test]
+fn test_burn_requires_admin() {
+    let env = Env::default();
+    let (_, token) = setup(&env);
+    let holder = Address::generate(&env);
+    let spender = Address::generate(&env);
+    token.mint(&holder, &1_000);
+    token.approve(&holder, &spender, &500, &100);
+
+    // Holders sign for themselves, but without the liquidity contract the burn is refused
+    env.mock_auths(&[MockAuth {
+        address: &holder,
+        invoke: &MockAuthInvoke {
+            contract: &token.address,
+            fn_name: "burn",
+            args: (&holder, 100i128).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(token.try_burn(&holder, &100).is_err());
+    env.mock_auths(&[MockAuth {
+        address: &spender,
+        invoke: &MockAuthInvoke {
+            contract: &token.address,
+            fn_name: "burn_from",
+            args: (&spender, &holder, 100i128).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(token.try_burn_from(&spender, &holder, &100).is_err());
+    assert_eq!((token.balance(&holder), token.total_supply()), (1_000, 1_000));
+}
+
+#[test]
+fn test_mint_requires_admin() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let token = LpTokenContractClient::new(&env, &env.register(LpTokenContract, ()));
+    token.initialize(&admin, &String::from_str(&env, "A_B"));
+
+    // No auths are mocked, so the admin's signature is missing
+    assert!(token.try_mint(&Address::generate(&env), &1_000).is_err());
+    assert_eq!(token.total_supply(), 0);
+}
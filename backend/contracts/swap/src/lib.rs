@@ -5,14 +5,24 @@
 //! Simplified swap execution contract focusing on:
 //! - Basic token swaps
 //! - Liquidity validation  
-//! - Fee calculation
+//! - Fee calculation, discounted by the fee manager's user tiers
+//! - Per-pool oracle price guards (circuit breaker)
+//! - Limit orders escrowed here and filled by keepers against pool liquidity
+//! - Dollar-cost-averaging schedules executed by anyone once due
 //! - Event emission
 //! 
-//! Route calculation and optimization is handled off-chain by frontend.
+//! Pool reserves are held by the liquidity contract, which custodies the tokens;
+//! this contract prices routes against them and settles each hop through it.
+//! 
+//! Routes are usually calculated off-chain by the frontend; `get_best_route`
+//! provides a bounded on-chain search through a few admin-set base tokens for
+//! other integrators, and `quote_exact_in` / `quote_exact_out` price a route
+//! exactly as execution will.
 
+use core::cmp::Ordering;
 use soroban_sdk::{
-    contract, contractimpl, contracttype, contracterror, contractmeta,
-    Address, Env, Vec, String, Symbol,
+    contract, contractclient, contractimpl, contracttype, contracterror, contractmeta,
+    Address, Bytes, Env, Vec, String, Symbol, token::TokenClient, xdr::ToXdr,
 };
 
 // Contract metadata
@@ -27,6 +37,19 @@ contractmeta!(key = "Version", val = "3.0.0");
 const BASE_FEE_RATE: u32 = 30;              // 0.3% base fee in basis points
 const MAX_SLIPPAGE: u32 = 1000;             // 10% max slippage
 const MIN_SWAP_AMOUNT: i128 = 1_000_000;    // 1 XLM minimum
+const MAX_ROUTE_HOPS: u32 = 4;              // Longest route searched on-chain
+const MAX_ROUTE_BASES: u32 = 3;             // Intermediate tokens the on-chain search may route through
+const MAX_PAIR_CANDIDATES: u32 = 3;         // Pools per token pair considered by the on-chain search
+const ROUTE_VALIDITY: u64 = 300;            // On-chain routes expire after 5 minutes
+const MAX_PAGE_SIZE: u32 = 50;              // Largest page returned by list_pools
+const FEE_TIERS: [u32; 3] = [5, 30, 100];   // Allowed fee tiers for permissionless pools
+const MAX_PRICE_DEVIATION: u32 = 5000;      // Widest configurable oracle deviation bound (50%)
+const FILLER_REWARD_BPS: u32 = 10;          // 0.1% of a limit order's output goes to the keeper filling it
+const MAX_USER_ORDERS: u32 = 50;            // Open limit orders per user
+const MAX_POOL_ORDERS: u32 = 100;           // Open limit orders per pool, bounding each fill_orders scan
+const MAX_USER_SCHEDULES: u32 = 20;         // Active DCA schedules per user
+const PRICE_PRECISION: i128 = 10_000_000;   // Scale of quoted spot prices
+const XLM_DECIMALS: u32 = 7;                // Fee tier volume is counted in XLM stroops
 
 /// Contract errors
 #[contracterror]
@@ -43,6 +66,17 @@ pub enum SwapError {
     PoolNotFound = 8,
     InsufficientLiquidity = 9,
     InvalidSlippage = 10,
+    PoolAlreadyExists = 11,
+    InvalidFeeTier = 12,
+    PriceDeviationExceeded = 13,
+    OracleUnavailable = 14,
+    FeeManagerUnavailable = 15,
+    InvalidOrder = 16,
+    OrderNotFound = 17,
+    OrderBookFull = 18,
+    InvalidSchedule = 19,
+    ScheduleNotFound = 20,
+    AlreadyInitialized = 21,
 }
 
 /// Simple token info for swaps
@@ -113,6 +147,8 @@ pub struct SwapRequest {
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct SwapResult {
+    /// Actual amount spent
+    pub amount_in: i128,
     /// Actual amount received
     pub amount_out: i128,
     /// Actual fees paid
@@ -123,6 +159,52 @@ pub struct SwapResult {
     pub executed_at: u64,
 }
 
+/// Quote for one hop of a route
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HopQuote {
+    /// Pool/exchange identifier
+    pub pool_id: String,
+    /// Input token
+    pub token_in: Token,
+    /// Output token
+    pub token_out: Token,
+    /// Amount entering the hop
+    pub amount_in: i128,
+    /// Amount leaving the hop
+    pub amount_out: i128,
+    /// Fee taken from the input
+    pub fee: i128,
+    /// Output per unit of input before the hop (raw units, scaled by PRICE_PRECISION)
+    pub spot_price_before: i128,
+    /// Output per unit of input after the hop, assuming the whole input stays in the pool
+    pub spot_price_after: i128,
+    /// Output lost to price impact, excluding fees (basis points)
+    pub price_impact_bps: u32,
+}
+
+/// Quote for a whole route, priced exactly as execution prices it
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwapQuote {
+    /// Per-hop breakdown, in route order
+    pub hops: Vec<HopQuote>,
+    /// Amount spent
+    pub amount_in: i128,
+    /// Amount received
+    pub amount_out: i128,
+    /// Fees across all hops
+    pub total_fees: i128,
+    /// Output lost to price impact across the route, excluding fees (basis points)
+    pub price_impact_bps: u32,
+    /// Least output to accept for the given tolerance (`min_amount_out` of execute_swap)
+    pub min_amount_out: i128,
+    /// Most input to accept for the given tolerance (`max_amount_in` of swap_tokens_for_exact_tokens)
+    pub max_amount_in: i128,
+    /// Quote timestamp
+    pub quoted_at: u64,
+}
+
 /// Liquidity pool info
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -133,9 +215,9 @@ pub struct Pool {
     pub token_a: Token,
     /// Token B
     pub token_b: Token,
-    /// Reserve A
+    /// Reserve A (mirrored from the liquidity contract)
     pub reserve_a: i128,
-    /// Reserve B
+    /// Reserve B (mirrored from the liquidity contract)
     pub reserve_b: i128,
     /// Pool fee (basis points)
     pub fee: u32,
@@ -151,12 +233,38 @@ pub enum DataKey {
     Admin,
     /// Pool data
     Pool(String),
+    /// IDs of all registered pools
+    PoolIds,
+    /// IDs of enabled pools for a token pair (canonically ordered)
+    PairPools(Token, Token),
+    /// Intermediate tokens searched by get_best_route
+    RouteBases,
     /// Supported tokens
     Token(String),
     /// Swap history
     SwapHistory(Address),
     /// Configuration
     Config,
+    /// Price oracle contract
+    Oracle,
+    /// Oracle price guard of a pool
+    PriceGuard(String),
+    /// Fee manager contract pricing per-user discounts
+    FeeManager,
+    /// Next limit order ID
+    NextOrderId,
+    /// Limit order by ID
+    Order(u64),
+    /// IDs of a user's open limit orders
+    UserOrders(Address),
+    /// IDs of a pool's open limit orders, oldest first
+    PoolOrders(String),
+    /// Next DCA schedule ID
+    NextScheduleId,
+    /// DCA schedule by ID
+    Schedule(u64),
+    /// IDs of a user's active DCA schedules
+    UserSchedules(Address),
 }
 
 /// Contract configuration
@@ -169,10 +277,180 @@ pub struct Config {
     pub max_slippage: u32,
     /// Minimum swap amount
     pub min_swap_amount: i128,
+    /// Liquidity contract holding pool reserves
+    pub liquidity_contract: Address,
     /// Contract enabled
     pub enabled: bool,
 }
 
+/// Oracle circuit breaker settings of a pool
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PriceGuard {
+    /// Largest allowed deviation of a hop's execution price from the oracle (basis points).
+    /// Execution prices include the pool fee and price impact, so the bound must leave room for both.
+    pub max_deviation_bps: u32,
+    /// Compare against the oracle TWAP instead of the spot price
+    pub use_twap: bool,
+}
+
+/// Limit order lifecycle
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OrderStatus {
+    Open,
+    Filled,
+    Cancelled,
+    Expired,
+}
+
+/// Limit order with its input escrowed in this contract
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LimitOrder {
+    /// Order identifier
+    pub id: u64,
+    /// Order owner
+    pub owner: Address,
+    /// Pool the order fills against
+    pub pool_id: String,
+    /// Token sold
+    pub token_in: Token,
+    /// Token bought
+    pub token_out: Token,
+    /// Escrowed amount sold
+    pub amount_in: i128,
+    /// Least the owner accepts for the whole input (the limit price)
+    pub min_amount_out: i128,
+    /// Amount the owner received, once filled
+    pub amount_out: i128,
+    /// Order status
+    pub status: OrderStatus,
+    /// Placement timestamp
+    pub created_at: u64,
+    /// Expiry timestamp
+    pub expires_at: u64,
+}
+
+/// DCA schedule lifecycle
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScheduleStatus {
+    Active,
+    Completed,
+    Cancelled,
+}
+
+/// Recurring swap with its unspent input escrowed in this contract
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DcaSchedule {
+    /// Schedule identifier
+    pub id: u64,
+    /// Schedule owner, who receives every installment's output
+    pub owner: Address,
+    /// Token sold
+    pub token_in: Token,
+    /// Token bought
+    pub token_out: Token,
+    /// Input swapped per installment
+    pub amount_per_period: i128,
+    /// Seconds between installments
+    pub period_secs: u64,
+    /// Total installments
+    pub periods: u32,
+    /// Installments executed so far
+    pub periods_executed: u32,
+    /// Largest shortfall of an installment's output from its oracle TWAP value, fees included (basis points)
+    pub max_slippage: u32,
+    /// Earliest time the next installment may run
+    pub next_execution: u64,
+    /// Total input spent
+    pub total_spent: i128,
+    /// Total output received
+    pub total_received: i128,
+    /// Schedule status
+    pub status: ScheduleStatus,
+    /// Creation timestamp
+    pub created_at: u64,
+}
+
+/// Oracle price source (mirrors the oracle contract)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PriceSource {
+    Chainlink,
+    RedStone,
+    StellarDEX,
+    Pyth,
+    Manual,
+}
+
+/// Aggregated oracle price (mirrors the oracle contract)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AggregatedPrice {
+    /// Asset symbol
+    pub asset: String,
+    /// Weighted average price (USD, 8 decimals)
+    pub price: i128,
+    /// Price timestamp
+    pub timestamp: u64,
+    /// Contributing sources
+    pub sources: Vec<PriceSource>,
+    /// Price confidence score
+    pub confidence_score: u32,
+    /// TWAP price
+    pub twap_price: i128,
+    /// Price deviation from previous
+    pub deviation: u32,
+}
+
+/// Pool reserves as reported by the liquidity contract
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolReserves {
+    /// Token A contract
+    pub token_a: Address,
+    /// Token B contract
+    pub token_b: Address,
+    /// Reserve A
+    pub reserve_a: i128,
+    /// Reserve B
+    pub reserve_b: i128,
+}
+
+/// Liquidity contract interface used for reserves and settlement
+#[contractclient(name = "LiquidityClient")]
+pub trait LiquidityContractInterface {
+    fn get_reserves(env: Env, pool_id: String) -> PoolReserves;
+    fn create_pool(env: Env, caller: Address, pool_id: String, token_a: Address, token_b: Address);
+    fn swap(
+        env: Env,
+        pool_id: String,
+        token_in: Address,
+        amount_in: i128,
+        fee: i128,
+        amount_out: i128,
+        to: Address,
+    );
+}
+
+/// Oracle contract interface used by pool price guards
+#[contractclient(name = "OracleClient")]
+pub trait OracleContractInterface {
+    fn get_price(env: Env, asset: String) -> AggregatedPrice;
+    fn get_twap_price(env: Env, asset: String) -> i128;
+}
+
+/// Fee manager interface used for tiered swap fees
+#[contractclient(name = "FeeManagerClient")]
+pub trait FeeManagerInterface {
+    fn calculate_swap_fee(env: Env, user: Address, amount: i128) -> i128;
+    fn update_user_volume(env: Env, user: Address, volume: i128, fees_paid: i128);
+    fn collect_and_distribute_fees(env: Env, caller: Address, total_fees: i128, fee_type: String);
+}
+
 #[contract]
 pub struct SwapContract;
 
@@ -182,7 +460,12 @@ impl SwapContract {
     pub fn initialize(
         env: Env,
         admin: Address,
+        liquidity_contract: Address,
     ) -> Result<(), SwapError> {
+        // Re-initializing could point settlement, and the escrow, at another liquidity contract
+        if env.storage().persistent().has(&DataKey::Admin) {
+            return Err(SwapError::AlreadyInitialized);
+        }
         admin.require_auth();
         
         // Set admin
@@ -193,6 +476,7 @@ impl SwapContract {
             base_fee_rate: BASE_FEE_RATE,
             max_slippage: MAX_SLIPPAGE,
             min_swap_amount: MIN_SWAP_AMOUNT,
+            liquidity_contract,
             enabled: true,
         };
         env.storage().persistent().set(&DataKey::Config, &config);
@@ -226,15 +510,11 @@ impl SwapContract {
             return Err(SwapError::InvalidRoute);
         }
         
-        // Execute swap steps
-        let mut actual_amount_out = 0i128;
-        let mut total_fees_paid = 0i128;
-        
-        for step in request.route.steps.iter() {
-            let step_result = Self::execute_swap_step(&env, &request.user, &step)?;
-            actual_amount_out += step_result.0;
-            total_fees_paid += step_result.1;
-        }
+        // Price each hop on the previous hop's output, at the user's fee tier
+        let fee_rate = Self::user_fee_rate(&env, &request.user);
+        let (amounts, fees) = Self::price_exact_in(&env, &config, &request.route.steps, request.route.total_amount_in, fee_rate)?;
+        let actual_amount_out = amounts.last_unchecked();
+        let total_fees_paid = Self::sum(&fees);
         
         // Check slippage protection
         if actual_amount_out < request.min_amount_out {
@@ -252,7 +532,11 @@ impl SwapContract {
             return Err(SwapError::SlippageExceeded);
         }
         
+        Self::settle_route(&env, &config, &request.route.steps, &amounts, &fees, &request.user, &request.user)?;
+        Self::report_swap_volume(&env, &request.user, &request.route.steps, &amounts, &fees);
+        
         let result = SwapResult {
+            amount_in: request.route.total_amount_in,
             amount_out: actual_amount_out,
             fees_paid: total_fees_paid,
             actual_slippage,
@@ -271,6 +555,77 @@ impl SwapContract {
         Ok(result)
     }
     
+    /// Swap as little input as needed to receive exactly `amount_out`
+    pub fn swap_tokens_for_exact_tokens(
+        env: Env,
+        user: Address,
+        route: SwapRoute,
+        amount_out: i128,
+        max_amount_in: i128,
+        deadline: u64,
+    ) -> Result<SwapResult, SwapError> {
+        if amount_out <= 0 || max_amount_in <= 0 {
+            return Err(SwapError::InvalidAmount);
+        }
+        
+        Self::validate_route(&route)?;
+        
+        user.require_auth();
+        
+        let config = Self::get_config(env.clone())?;
+        if !config.enabled {
+            return Err(SwapError::SwapFailed);
+        }
+        
+        // Check deadline
+        let current_time = env.ledger().timestamp();
+        if current_time > deadline {
+            return Err(SwapError::SwapFailed);
+        }
+        
+        // Check route expiry
+        if current_time > route.expires_at {
+            return Err(SwapError::InvalidRoute);
+        }
+        
+        // Work backwards from the desired output, at the user's fee tier
+        let fee_rate = Self::user_fee_rate(&env, &user);
+        let (amounts, fees) = Self::price_exact_out(&env, &config, &route.steps, amount_out, fee_rate)?;
+        let amount_in = amounts.first_unchecked();
+        let total_fees_paid = Self::sum(&fees);
+        
+        if amount_in < config.min_swap_amount {
+            return Err(SwapError::InvalidAmount);
+        }
+        
+        if amount_in > max_amount_in {
+            return Err(SwapError::SlippageExceeded);
+        }
+        
+        // Settle each hop for exactly the amounts computed above
+        Self::settle_route(&env, &config, &route.steps, &amounts, &fees, &user, &user)?;
+        Self::report_swap_volume(&env, &user, &route.steps, &amounts, &fees);
+        
+        let result = SwapResult {
+            amount_in,
+            amount_out,
+            fees_paid: total_fees_paid,
+            actual_slippage: Self::calculate_slippage(route.total_amount_in, amount_in)?,
+            executed_at: current_time,
+        };
+        
+        // Record swap history
+        Self::record_swap_history(&env, &user, &result)?;
+        
+        // Emit swap event
+        env.events().publish(
+            (Symbol::new(&env, "swap_executed"),),
+            (user, result.clone())
+        );
+        
+        Ok(result)
+    }
+    
     /// Add liquidity pool
     pub fn add_pool(
         env: Env,
@@ -284,37 +639,108 @@ impl SwapContract {
         
         caller.require_auth();
         
-        env.storage().persistent().set(&DataKey::Pool(pool.id.clone()), &pool);
+        // The liquidity contract must hold a pool with the same ID and token order
+        let config = Self::get_config(env.clone())?;
+        let reserves = Self::get_reserves(&env, &config, &pool.id)?;
+        if pool.token_a.address != Some(reserves.token_a) || pool.token_b.address != Some(reserves.token_b) {
+            return Err(SwapError::InvalidRoute);
+        }
+        
+        let pool_key = DataKey::Pool(pool.id.clone());
+        if let Some(existing) = env.storage().persistent().get::<DataKey, Pool>(&pool_key) {
+            // Re-registering: drop the old pair entry in case the tokens changed
+            Self::unindex_pair(&env, &existing);
+        } else {
+            let mut pool_ids = Self::get_pool_ids(&env);
+            pool_ids.push_back(pool.id.clone());
+            env.storage().persistent().set(&DataKey::PoolIds, &pool_ids);
+        }
+        
+        if pool.enabled {
+            Self::index_pair(&env, &pool);
+        }
+        
+        env.storage().persistent().set(&pool_key, &pool);
         
         Ok(())
     }
     
-    /// Get pool information
-    pub fn get_pool(
-        env: Env,
-        pool_id: String,
-    ) -> Result<Pool, SwapError> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::Pool(pool_id))
-            .ok_or(SwapError::PoolNotFound)
-    }
-    
-    /// Get configuration
-    pub fn get_config(
+    /// Create a constant-product pool for a token pair (permissionless).
+    /// Only the token addresses are taken from the caller; symbols and decimals are read
+    /// from the token contracts, since price guards look the symbols up in the oracle.
+    pub fn create_pool(
         env: Env,
-    ) -> Result<Config, SwapError> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::Config)
-            .ok_or(SwapError::SwapFailed)
+        token_a: Token,
+        token_b: Token,
+        fee_tier: u32,
+    ) -> Result<String, SwapError> {
+        let address_a = token_a.address.ok_or(SwapError::TokenNotSupported)?;
+        let address_b = token_b.address.ok_or(SwapError::TokenNotSupported)?;
+        if address_a == address_b {
+            return Err(SwapError::InvalidRoute);
+        }
+        let token_a = Self::read_token(&env, &address_a)?;
+        let token_b = Self::read_token(&env, &address_b)?;
+        
+        if !FEE_TIERS.contains(&fee_tier) {
+            return Err(SwapError::InvalidFeeTier);
+        }
+        
+        let (token_a, token_b) = Self::sort_tokens(token_a, token_b);
+        let pool_id = Self::derive_pool_id(&env, &token_a, &token_b, fee_tier)?;
+        
+        let pool_key = DataKey::Pool(pool_id.clone());
+        if env.storage().persistent().has(&pool_key) {
+            return Err(SwapError::PoolAlreadyExists);
+        }
+        
+        // Reserves live in the liquidity contract; LPs deposit there
+        let config = Self::get_config(env.clone())?;
+        let liquidity = LiquidityClient::new(&env, &config.liquidity_contract);
+        if let Ok(Ok(_)) = liquidity.try_get_reserves(&pool_id) {
+            return Err(SwapError::PoolAlreadyExists);
+        }
+        
+        let (address_a, address_b) = if token_a.address == Some(address_a.clone()) {
+            (address_a, address_b)
+        } else {
+            (address_b, address_a)
+        };
+        liquidity
+            .try_create_pool(&env.current_contract_address(), &pool_id, &address_a, &address_b)
+            .map_err(|_| SwapError::SwapFailed)?
+            .map_err(|_| SwapError::SwapFailed)?;
+        
+        let pool = Pool {
+            id: pool_id.clone(),
+            token_a,
+            token_b,
+            reserve_a: 0,
+            reserve_b: 0,
+            fee: fee_tier,
+            enabled: true,
+        };
+        
+        let mut pool_ids = Self::get_pool_ids(&env);
+        pool_ids.push_back(pool_id.clone());
+        env.storage().persistent().set(&DataKey::PoolIds, &pool_ids);
+        Self::index_pair(&env, &pool);
+        env.storage().persistent().set(&pool_key, &pool);
+        
+        env.events().publish(
+            (Symbol::new(&env, "pool_created"),),
+            (pool_id.clone(), pool.token_a, pool.token_b, fee_tier)
+        );
+        
+        Ok(pool_id)
     }
     
-    /// Update configuration (admin only)
-    pub fn update_config(
+    /// Enable or disable a pool (admin only)
+    pub fn set_pool_enabled(
         env: Env,
         caller: Address,
-        config: Config,
+        pool_id: String,
+        enabled: bool,
     ) -> Result<(), SwapError> {
         let admin = Self::get_admin(&env)?;
         if caller != admin {
@@ -322,99 +748,1352 @@ impl SwapContract {
         }
         
         caller.require_auth();
-        env.storage().persistent().set(&DataKey::Config, &config);
+        
+        let mut pool = Self::read_pool(&env, &pool_id)?;
+        if pool.enabled == enabled {
+            return Ok(());
+        }
+        
+        pool.enabled = enabled;
+        if enabled {
+            Self::index_pair(&env, &pool);
+        } else {
+            Self::unindex_pair(&env, &pool);
+        }
+        
+        env.storage().persistent().set(&DataKey::Pool(pool_id), &pool);
         
         Ok(())
     }
-
-    // Internal helper functions
     
-    fn validate_swap_request(
-        env: &Env,
-        request: &SwapRequest,
+    /// Remove a pool from the registry (admin only)
+    pub fn remove_pool(
+        env: Env,
+        caller: Address,
+        pool_id: String,
     ) -> Result<(), SwapError> {
-        let config = Self::get_config(env.clone())?;
-        
-        // Check minimum amount
-        if request.route.total_amount_in < config.min_swap_amount {
-            return Err(SwapError::InvalidAmount);
+        let admin = Self::get_admin(&env)?;
+        if caller != admin {
+            return Err(SwapError::Unauthorized);
         }
         
-        // Check slippage tolerance
-        if request.max_slippage > config.max_slippage {
-            return Err(SwapError::InvalidSlippage);
-        }
+        caller.require_auth();
         
-        // Validate route has steps
-        if request.route.steps.is_empty() {
-            return Err(SwapError::InvalidRoute);
+        let pool = Self::read_pool(&env, &pool_id)?;
+        Self::unindex_pair(&env, &pool);
+        
+        let mut pool_ids = Self::get_pool_ids(&env);
+        if let Some(index) = pool_ids.first_index_of(&pool_id) {
+            pool_ids.remove(index);
+            env.storage().persistent().set(&DataKey::PoolIds, &pool_ids);
         }
         
+        env.storage().persistent().remove(&DataKey::Pool(pool_id));
+        
         Ok(())
     }
     
-    fn execute_swap_step(
-        env: &Env,
-        _user: &Address,
-        step: &SwapStep,
-    ) -> Result<(i128, i128), SwapError> {
-        // Get pool info
-        let pool = Self::get_pool(env.clone(), step.pool_id.clone())?;
+    /// List registered pools, paginated in registration order
+    pub fn list_pools(
+        env: Env,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<Pool>, SwapError> {
+        let pool_ids = Self::get_pool_ids(&env);
+        let end = offset.saturating_add(limit.min(MAX_PAGE_SIZE)).min(pool_ids.len());
         
-        if !pool.enabled {
-            return Err(SwapError::PoolNotFound);
+        let mut pools = Vec::new(&env);
+        for i in offset..end {
+            pools.push_back(Self::get_pool(env.clone(), pool_ids.get_unchecked(i))?);
         }
         
-        // Validate pool tokens match step
-        let valid_pair = (pool.token_a == step.token_in && pool.token_b == step.token_out) ||
-                        (pool.token_b == step.token_in && pool.token_a == step.token_out);
+        Ok(pools)
+    }
+    
+    /// Get enabled pools trading a token pair, in either order
+    pub fn get_pools_for_pair(
+        env: Env,
+        token_a: Token,
+        token_b: Token,
+    ) -> Result<Vec<Pool>, SwapError> {
+        let mut pools = Vec::new(&env);
+        for pool_id in Self::pair_pool_ids(&env, token_a, token_b).iter() {
+            pools.push_back(Self::get_pool(env.clone(), pool_id)?);
+        }
         
-        if !valid_pair {
+        Ok(pools)
+    }
+    
+    /// Find the route with the highest output through the pair's own pools and the route bases.
+    /// Only pools between the two tokens and the bases are searched, so the cost stays bounded
+    /// however many pools are created.
+    pub fn get_best_route(
+        env: Env,
+        token_in: Token,
+        token_out: Token,
+        amount_in: i128,
+        max_hops: u32,
+    ) -> Result<SwapRoute, SwapError> {
+        if amount_in <= 0 {
+            return Err(SwapError::InvalidAmount);
+        }
+        
+        if token_in == token_out || max_hops == 0 || max_hops > MAX_ROUTE_HOPS {
             return Err(SwapError::InvalidRoute);
         }
         
-        // Calculate actual output using AMM formula
-        let actual_amount_out = Self::calculate_amm_output(
-            step.amount_in,
-            &pool,
-            &step.token_in,
-        )?;
+        let mut tokens = Vec::from_array(&env, [token_in.clone(), token_out.clone()]);
+        for base in Self::get_route_bases(env.clone()).iter() {
+            if !tokens.contains(&base) {
+                tokens.push_back(base);
+            }
+        }
         
-        // Calculate fee
-        let fee = (step.amount_in * pool.fee as i128) / 10000;
+        // Load the enabled pools between every pair of those tokens once; the search works on this snapshot
+        let mut pools: Vec<Pool> = Vec::new(&env);
+        for i in 0..tokens.len() {
+            for j in (i + 1)..tokens.len() {
+                let pool_ids = Self::pair_pool_ids(&env, tokens.get_unchecked(i), tokens.get_unchecked(j));
+                for pool_id in pool_ids.iter().take(MAX_PAIR_CANDIDATES as usize) {
+                    pools.push_back(Self::get_pool(env.clone(), pool_id)?);
+                }
+            }
+        }
         
-        // Execute token transfers (simplified - would integrate with token contracts)
-        // This would call the actual token contract transfer functions
+        let mut path: Vec<SwapStep> = Vec::new(&env);
+        let mut best_steps: Vec<SwapStep> = Vec::new(&env);
+        let mut best_amount_out = 0i128;
         
-        Ok((actual_amount_out, fee))
-    }
-    
-    fn calculate_amm_output(
-        amount_in: i128,
-        pool: &Pool,
-        token_in: &Token,
-    ) -> Result<i128, SwapError> {
-        // Simple AMM formula: x * y = k
-        let (reserve_in, reserve_out) = if pool.token_a == *token_in {
-            (pool.reserve_a, pool.reserve_b)
-        } else {
-            (pool.reserve_b, pool.reserve_a)
-        };
+        Self::search_routes(
+            &pools,
+            &token_in,
+            &token_out,
+            amount_in,
+            max_hops,
+            &mut path,
+            &mut best_steps,
+            &mut best_amount_out,
+        );
         
-        if reserve_in == 0 || reserve_out == 0 {
-            return Err(SwapError::InsufficientLiquidity);
+        if best_steps.is_empty() {
+            return Err(SwapError::InvalidRoute);
         }
         
-        // amount_out = (amount_in * reserve_out) / (reserve_in + amount_in)
-        let amount_out = (amount_in * reserve_out) / (reserve_in + amount_in);
-        
-        if amount_out <= 0 {
-            return Err(SwapError::InsufficientLiquidity);
+        let mut total_fees = 0i128;
+        for step in best_steps.iter() {
+            total_fees += step.fee;
+        }
+        
+        Ok(SwapRoute {
+            steps: best_steps,
+            total_amount_in: amount_in,
+            total_amount_out: best_amount_out,
+            total_fees,
+            expected_slippage: 0,
+            expires_at: env.ledger().timestamp() + ROUTE_VALIDITY,
+        })
+    }
+    
+    /// Set the intermediate tokens get_best_route may route through (admin only)
+    pub fn set_route_bases(
+        env: Env,
+        caller: Address,
+        bases: Vec<Token>,
+    ) -> Result<(), SwapError> {
+        let admin = Self::get_admin(&env)?;
+        if caller != admin {
+            return Err(SwapError::Unauthorized);
+        }
+        
+        caller.require_auth();
+        
+        if bases.len() > MAX_ROUTE_BASES {
+            return Err(SwapError::InvalidRoute);
+        }
+        
+        env.storage().persistent().set(&DataKey::RouteBases, &bases);
+        
+        Ok(())
+    }
+    
+    /// Get the intermediate tokens get_best_route may route through
+    pub fn get_route_bases(env: Env) -> Vec<Token> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::RouteBases)
+            .unwrap_or(Vec::new(&env))
+    }
+    
+    /// Quote selling `route.total_amount_in` along a route at `user`'s fee tier
+    pub fn quote_exact_in(
+        env: Env,
+        user: Address,
+        route: SwapRoute,
+        slippage_tolerance: u32,
+    ) -> Result<SwapQuote, SwapError> {
+        let config = Self::get_config(env.clone())?;
+        Self::validate_route(&route)?;
+        
+        let fee_rate = Self::user_fee_rate(&env, &user);
+        let (amounts, fees) = Self::price_exact_in(&env, &config, &route.steps, route.total_amount_in, fee_rate)?;
+        
+        Self::build_quote(&env, &config, &route.steps, &amounts, &fees, fee_rate, slippage_tolerance)
+    }
+    
+    /// Quote buying exactly `route.total_amount_out` along a route at `user`'s fee tier
+    pub fn quote_exact_out(
+        env: Env,
+        user: Address,
+        route: SwapRoute,
+        slippage_tolerance: u32,
+    ) -> Result<SwapQuote, SwapError> {
+        let config = Self::get_config(env.clone())?;
+        Self::validate_route(&route)?;
+        
+        if route.total_amount_out <= 0 {
+            return Err(SwapError::InvalidAmount);
+        }
+        
+        let fee_rate = Self::user_fee_rate(&env, &user);
+        let (amounts, fees) = Self::price_exact_out(&env, &config, &route.steps, route.total_amount_out, fee_rate)?;
+        
+        Self::build_quote(&env, &config, &route.steps, &amounts, &fees, fee_rate, slippage_tolerance)
+    }
+    
+    /// Get pool information with current reserves
+    pub fn get_pool(
+        env: Env,
+        pool_id: String,
+    ) -> Result<Pool, SwapError> {
+        let mut pool = Self::read_pool(&env, &pool_id)?;
+        
+        let config = Self::get_config(env.clone())?;
+        let reserves = Self::get_reserves(&env, &config, &pool.id)?;
+        pool.reserve_a = reserves.reserve_a;
+        pool.reserve_b = reserves.reserve_b;
+        
+        Ok(pool)
+    }
+    
+    /// Get configuration
+    pub fn get_config(
+        env: Env,
+    ) -> Result<Config, SwapError> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Config)
+            .ok_or(SwapError::SwapFailed)
+    }
+    
+    /// Update configuration (admin only)
+    pub fn update_config(
+        env: Env,
+        caller: Address,
+        config: Config,
+    ) -> Result<(), SwapError> {
+        let admin = Self::get_admin(&env)?;
+        if caller != admin {
+            return Err(SwapError::Unauthorized);
+        }
+        
+        caller.require_auth();
+        env.storage().persistent().set(&DataKey::Config, &config);
+        
+        Ok(())
+    }
+
+    /// Set the price oracle used by pool price guards (admin only)
+    pub fn set_oracle(
+        env: Env,
+        caller: Address,
+        oracle: Address,
+    ) -> Result<(), SwapError> {
+        let admin = Self::get_admin(&env)?;
+        if caller != admin {
+            return Err(SwapError::Unauthorized);
+        }
+        
+        caller.require_auth();
+        env.storage().persistent().set(&DataKey::Oracle, &oracle);
+        
+        Ok(())
+    }
+    
+    /// Set the fee manager whose user tiers discount swap fees (admin only)
+    pub fn set_fee_manager(
+        env: Env,
+        caller: Address,
+        fee_manager: Address,
+    ) -> Result<(), SwapError> {
+        let admin = Self::get_admin(&env)?;
+        if caller != admin {
+            return Err(SwapError::Unauthorized);
+        }
+        
+        caller.require_auth();
+        env.storage().persistent().set(&DataKey::FeeManager, &fee_manager);
+        
+        Ok(())
+    }
+    
+    /// Guard a pool against trades priced away from the oracle (admin only).
+    /// A bound of zero removes the guard. Token symbols are used as oracle assets.
+    pub fn set_price_guard(
+        env: Env,
+        caller: Address,
+        pool_id: String,
+        max_deviation_bps: u32,
+        use_twap: bool,
+    ) -> Result<(), SwapError> {
+        let admin = Self::get_admin(&env)?;
+        if caller != admin {
+            return Err(SwapError::Unauthorized);
+        }
+        
+        caller.require_auth();
+        
+        Self::read_pool(&env, &pool_id)?;
+        if max_deviation_bps > MAX_PRICE_DEVIATION {
+            return Err(SwapError::InvalidSlippage);
+        }
+        
+        let key = DataKey::PriceGuard(pool_id);
+        if max_deviation_bps == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &PriceGuard { max_deviation_bps, use_twap });
+        }
+        
+        Ok(())
+    }
+    
+    /// Get a pool's price guard, if any
+    pub fn get_price_guard(env: Env, pool_id: String) -> Option<PriceGuard> {
+        env.storage().persistent().get(&DataKey::PriceGuard(pool_id))
+    }
+
+    /// Escrow `amount_in` of a pool token, to be sold once the pool pays at least
+    /// `min_amount_out` for all of it after the filler reward
+    pub fn place_limit_order(
+        env: Env,
+        owner: Address,
+        pool_id: String,
+        token_in: Token,
+        amount_in: i128,
+        min_amount_out: i128,
+        expires_at: u64,
+    ) -> Result<u64, SwapError> {
+        owner.require_auth();
+        
+        let config = Self::get_config(env.clone())?;
+        if !config.enabled {
+            return Err(SwapError::SwapFailed);
+        }
+        
+        if amount_in < config.min_swap_amount || min_amount_out <= 0 {
+            return Err(SwapError::InvalidAmount);
+        }
+        
+        let current_time = env.ledger().timestamp();
+        if expires_at <= current_time {
+            return Err(SwapError::InvalidOrder);
+        }
+        
+        let pool = Self::read_pool(&env, &pool_id)?;
+        if !pool.enabled {
+            return Err(SwapError::PoolNotFound);
+        }
+        
+        let token_out = if pool.token_a == token_in {
+            pool.token_b
+        } else if pool.token_b == token_in {
+            pool.token_a
+        } else {
+            return Err(SwapError::TokenNotSupported);
+        };
+        let token_address = token_in.address.clone().ok_or(SwapError::TokenNotSupported)?;
+        
+        let user_key = DataKey::UserOrders(owner.clone());
+        let pool_key = DataKey::PoolOrders(pool_id.clone());
+        let mut user_orders = Self::id_index(&env, &user_key);
+        let mut pool_orders = Self::id_index(&env, &pool_key);
+        if user_orders.len() >= MAX_USER_ORDERS || pool_orders.len() >= MAX_POOL_ORDERS {
+            return Err(SwapError::OrderBookFull);
+        }
+        
+        // Escrow the input until the order fills, expires or is cancelled
+        TokenClient::new(&env, &token_address).transfer(&owner, &env.current_contract_address(), &amount_in);
+        
+        let order_id: u64 = env.storage().persistent().get(&DataKey::NextOrderId).unwrap_or(1);
+        env.storage().persistent().set(&DataKey::NextOrderId, &(order_id + 1));
+        
+        let order = LimitOrder {
+            id: order_id,
+            owner: owner.clone(),
+            pool_id,
+            token_in,
+            token_out,
+            amount_in,
+            min_amount_out,
+            amount_out: 0,
+            status: OrderStatus::Open,
+            created_at: current_time,
+            expires_at,
+        };
+        env.storage().persistent().set(&DataKey::Order(order_id), &order);
+        
+        user_orders.push_back(order_id);
+        pool_orders.push_back(order_id);
+        env.storage().persistent().set(&user_key, &user_orders);
+        env.storage().persistent().set(&pool_key, &pool_orders);
+        
+        env.events().publish(
+            (Symbol::new(&env, "order_placed"),),
+            (owner, order)
+        );
+        
+        Ok(order_id)
+    }
+    
+    /// Cancel an open limit order and refund its escrow
+    pub fn cancel_order(
+        env: Env,
+        owner: Address,
+        order_id: u64,
+    ) -> Result<(), SwapError> {
+        owner.require_auth();
+        
+        let mut order = Self::get_order(env.clone(), order_id)?;
+        if order.owner != owner {
+            return Err(SwapError::Unauthorized);
+        }
+        if order.status != OrderStatus::Open {
+            return Err(SwapError::InvalidOrder);
+        }
+        
+        Self::refund_order(&env, &order)?;
+        order.status = OrderStatus::Cancelled;
+        Self::close_order(&env, &order);
+        
+        env.events().publish(
+            (Symbol::new(&env, "order_cancelled"),),
+            (owner, order_id)
+        );
+        
+        Ok(())
+    }
+    
+    /// Fill up to `max_orders` of a pool's open limit orders whose limit the pool now meets,
+    /// oldest first. Expired orders met along the way are refunded. The keeper earns
+    /// FILLER_REWARD_BPS of each filled order's output.
+    pub fn fill_orders(
+        env: Env,
+        keeper: Address,
+        pool_id: String,
+        max_orders: u32,
+    ) -> Result<Vec<u64>, SwapError> {
+        keeper.require_auth();
+        
+        let config = Self::get_config(env.clone())?;
+        if !config.enabled {
+            return Err(SwapError::SwapFailed);
+        }
+        
+        let contract = env.current_contract_address();
+        let current_time = env.ledger().timestamp();
+        let mut filled = Vec::new(&env);
+        
+        for order_id in Self::id_index(&env, &DataKey::PoolOrders(pool_id)).iter() {
+            if filled.len() >= max_orders {
+                break;
+            }
+            
+            let mut order = Self::get_order(env.clone(), order_id)?;
+            if current_time > order.expires_at {
+                Self::refund_order(&env, &order)?;
+                order.status = OrderStatus::Expired;
+                Self::close_order(&env, &order);
+                
+                env.events().publish(
+                    (Symbol::new(&env, "order_expired"),),
+                    (order.owner, order_id)
+                );
+                continue;
+            }
+            
+            // Price the order on current reserves at the owner's fee tier
+            let step = SwapStep {
+                token_in: order.token_in.clone(),
+                token_out: order.token_out.clone(),
+                pool_id: order.pool_id.clone(),
+                amount_in: order.amount_in,
+                amount_out: 0,
+                fee: 0,
+            };
+            let steps = Vec::from_array(&env, [step.clone()]);
+            let fee_rate = Self::user_fee_rate(&env, &order.owner);
+            let Ok((amounts, fees)) = Self::price_exact_in(&env, &config, &steps, order.amount_in, fee_rate) else {
+                continue;
+            };
+            
+            let amount_out = amounts.last_unchecked();
+            let reward = amount_out * FILLER_REWARD_BPS as i128 / 10000;
+            if amount_out - reward < order.min_amount_out {
+                continue;
+            }
+            
+            // A tripped price guard skips the order instead of failing the whole batch
+            if Self::check_price_guard(&env, &step, order.amount_in, amount_out).is_err() {
+                continue;
+            }
+            
+            Self::settle_route(&env, &config, &steps, &amounts, &fees, &contract, &contract)?;
+            
+            let token_out = order.token_out.address.clone().ok_or(SwapError::TokenNotSupported)?;
+            let token_client = TokenClient::new(&env, &token_out);
+            token_client.transfer(&contract, &order.owner, &(amount_out - reward));
+            if reward > 0 {
+                token_client.transfer(&contract, &keeper, &reward);
+            }
+            
+            let total_fees_paid = Self::sum(&fees);
+            Self::report_swap_volume(&env, &order.owner, &steps, &amounts, &fees);
+            
+            order.amount_out = amount_out - reward;
+            order.status = OrderStatus::Filled;
+            Self::close_order(&env, &order);
+            
+            let result = SwapResult {
+                amount_in: order.amount_in,
+                amount_out: order.amount_out,
+                fees_paid: total_fees_paid,
+                actual_slippage: 0,
+                executed_at: current_time,
+            };
+            Self::record_swap_history(&env, &order.owner, &result)?;
+            
+            env.events().publish(
+                (Symbol::new(&env, "order_filled"),),
+                (order.owner, order_id, keeper.clone(), result, reward)
+            );
+            
+            filled.push_back(order_id);
+        }
+        
+        Ok(filled)
+    }
+    
+    /// Get a limit order
+    pub fn get_order(env: Env, order_id: u64) -> Result<LimitOrder, SwapError> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Order(order_id))
+            .ok_or(SwapError::OrderNotFound)
+    }
+    
+    /// Get a user's open limit orders
+    pub fn get_user_orders(env: Env, user: Address) -> Vec<LimitOrder> {
+        Self::load_orders(&env, &DataKey::UserOrders(user))
+    }
+    
+    /// Get a pool's open limit orders, oldest first
+    pub fn get_pool_orders(env: Env, pool_id: String) -> Vec<LimitOrder> {
+        Self::load_orders(&env, &DataKey::PoolOrders(pool_id))
+    }
+
+    /// Escrow `amount_per_period * periods` of `token_in` and swap one installment into
+    /// `token_out` every `period_secs`, starting now. Installments are routed on-chain and
+    /// skipped while their output falls more than `max_slippage` short of its oracle TWAP value.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_dca(
+        env: Env,
+        owner: Address,
+        token_in: Token,
+        token_out: Token,
+        amount_per_period: i128,
+        period_secs: u64,
+        periods: u32,
+        max_slippage: u32,
+    ) -> Result<u64, SwapError> {
+        owner.require_auth();
+        
+        let config = Self::get_config(env.clone())?;
+        if !config.enabled {
+            return Err(SwapError::SwapFailed);
+        }
+        
+        if amount_per_period < config.min_swap_amount {
+            return Err(SwapError::InvalidAmount);
+        }
+        
+        if max_slippage > config.max_slippage {
+            return Err(SwapError::InvalidSlippage);
+        }
+        
+        if period_secs == 0 || periods == 0 || token_in == token_out {
+            return Err(SwapError::InvalidSchedule);
+        }
+        
+        let token_address = token_in.address.clone().ok_or(SwapError::TokenNotSupported)?;
+        token_out.address.as_ref().ok_or(SwapError::TokenNotSupported)?;
+        let total_amount = amount_per_period
+            .checked_mul(periods as i128)
+            .ok_or(SwapError::InvalidAmount)?;
+        
+        let user_key = DataKey::UserSchedules(owner.clone());
+        let mut user_schedules = Self::id_index(&env, &user_key);
+        if user_schedules.len() >= MAX_USER_SCHEDULES {
+            return Err(SwapError::OrderBookFull);
+        }
+        
+        // Escrow every installment up front
+        TokenClient::new(&env, &token_address).transfer(&owner, &env.current_contract_address(), &total_amount);
+        
+        let schedule_id: u64 = env.storage().persistent().get(&DataKey::NextScheduleId).unwrap_or(1);
+        env.storage().persistent().set(&DataKey::NextScheduleId, &(schedule_id + 1));
+        
+        let current_time = env.ledger().timestamp();
+        let schedule = DcaSchedule {
+            id: schedule_id,
+            owner: owner.clone(),
+            token_in,
+            token_out,
+            amount_per_period,
+            period_secs,
+            periods,
+            periods_executed: 0,
+            max_slippage,
+            next_execution: current_time,
+            total_spent: 0,
+            total_received: 0,
+            status: ScheduleStatus::Active,
+            created_at: current_time,
+        };
+        env.storage().persistent().set(&DataKey::Schedule(schedule_id), &schedule);
+        
+        user_schedules.push_back(schedule_id);
+        env.storage().persistent().set(&user_key, &user_schedules);
+        
+        env.events().publish(
+            (Symbol::new(&env, "dca_created"),),
+            (owner, schedule)
+        );
+        
+        Ok(schedule_id)
+    }
+    
+    /// Run the due installment of each listed schedule; anyone may call this.
+    /// Unknown schedules and those that are not due, cannot be routed or priced, or would
+    /// exceed their slippage bound are skipped. Returns the IDs of the schedules that executed.
+    pub fn execute_due_dca(
+        env: Env,
+        schedule_ids: Vec<u64>,
+    ) -> Result<Vec<u64>, SwapError> {
+        let config = Self::get_config(env.clone())?;
+        if !config.enabled {
+            return Err(SwapError::SwapFailed);
+        }
+        
+        // Installments are bounded by the oracle, which the caller cannot move within the transaction
+        let oracle: Address = env.storage().persistent()
+            .get(&DataKey::Oracle)
+            .ok_or(SwapError::OracleUnavailable)?;
+        
+        let contract = env.current_contract_address();
+        let current_time = env.ledger().timestamp();
+        let mut executed = Vec::new(&env);
+        
+        for schedule_id in schedule_ids.iter() {
+            let Ok(mut schedule) = Self::get_dca(env.clone(), schedule_id) else {
+                continue;
+            };
+            if schedule.status != ScheduleStatus::Active || current_time < schedule.next_execution {
+                continue;
+            }
+            
+            let amount_in = schedule.amount_per_period;
+            let Ok(route) = Self::get_best_route(
+                env.clone(),
+                schedule.token_in.clone(),
+                schedule.token_out.clone(),
+                amount_in,
+                MAX_ROUTE_HOPS,
+            ) else {
+                continue;
+            };
+            
+            // Same pricing as execute_swap, at the owner's fee tier
+            let fee_rate = Self::user_fee_rate(&env, &schedule.owner);
+            let Ok((amounts, fees)) = Self::price_exact_in(&env, &config, &route.steps, amount_in, fee_rate) else {
+                continue;
+            };
+            let amount_out = amounts.last_unchecked();
+            
+            // Slippage is the shortfall from the oracle TWAP value, so pool prices moved just
+            // before execution cannot pass the check
+            let Ok(reference_out) = Self::oracle_amount_out(&env, &oracle, &schedule.token_in, &schedule.token_out, amount_in, true) else {
+                continue;
+            };
+            let actual_slippage = if amount_out >= reference_out {
+                0
+            } else {
+                Self::calculate_slippage(reference_out, amount_out)?
+            };
+            if reference_out <= 0 || actual_slippage > schedule.max_slippage {
+                continue;
+            }
+            
+            // A tripped price guard skips the installment instead of failing the whole batch
+            let mut guarded = false;
+            for i in 0..route.steps.len() {
+                let step = route.steps.get_unchecked(i);
+                if Self::check_price_guard(&env, &step, amounts.get_unchecked(i), amounts.get_unchecked(i + 1)).is_err() {
+                    guarded = true;
+                    break;
+                }
+            }
+            if guarded {
+                continue;
+            }
+            
+            Self::settle_route(&env, &config, &route.steps, &amounts, &fees, &contract, &schedule.owner)?;
+            
+            let total_fees_paid = Self::sum(&fees);
+            Self::report_swap_volume(&env, &schedule.owner, &route.steps, &amounts, &fees);
+            
+            let result = SwapResult {
+                amount_in,
+                amount_out,
+                fees_paid: total_fees_paid,
+                actual_slippage,
+                executed_at: current_time,
+            };
+            Self::record_swap_history(&env, &schedule.owner, &result)?;
+            
+            schedule.periods_executed += 1;
+            schedule.total_spent += amount_in;
+            schedule.total_received += amount_out;
+            
+            // Late keepers do not bunch installments: skip ahead to the next period still in the future
+            let missed = (current_time - schedule.next_execution) / schedule.period_secs + 1;
+            schedule.next_execution = schedule.next_execution
+                .saturating_add(missed.saturating_mul(schedule.period_secs));
+            
+            if schedule.periods_executed >= schedule.periods {
+                schedule.status = ScheduleStatus::Completed;
+                Self::close_schedule(&env, &schedule);
+            } else {
+                env.storage().persistent().set(&DataKey::Schedule(schedule_id), &schedule);
+            }
+            
+            env.events().publish(
+                (Symbol::new(&env, "dca_executed"),),
+                (schedule.owner, schedule_id, result)
+            );
+            
+            executed.push_back(schedule_id);
+        }
+        
+        Ok(executed)
+    }
+    
+    /// Cancel an active DCA schedule and refund its unspent escrow
+    pub fn cancel_dca(
+        env: Env,
+        owner: Address,
+        schedule_id: u64,
+    ) -> Result<i128, SwapError> {
+        owner.require_auth();
+        
+        let mut schedule = Self::get_dca(env.clone(), schedule_id)?;
+        if schedule.owner != owner {
+            return Err(SwapError::Unauthorized);
+        }
+        if schedule.status != ScheduleStatus::Active {
+            return Err(SwapError::InvalidSchedule);
+        }
+        
+        let remaining_periods = (schedule.periods - schedule.periods_executed) as i128;
+        let refund = schedule.amount_per_period * remaining_periods;
+        let token_in = schedule.token_in.address.clone().ok_or(SwapError::TokenNotSupported)?;
+        TokenClient::new(&env, &token_in).transfer(&env.current_contract_address(), &owner, &refund);
+        
+        schedule.status = ScheduleStatus::Cancelled;
+        Self::close_schedule(&env, &schedule);
+        
+        env.events().publish(
+            (Symbol::new(&env, "dca_cancelled"),),
+            (owner, schedule_id, refund)
+        );
+        
+        Ok(refund)
+    }
+    
+    /// Get a DCA schedule
+    pub fn get_dca(env: Env, schedule_id: u64) -> Result<DcaSchedule, SwapError> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Schedule(schedule_id))
+            .ok_or(SwapError::ScheduleNotFound)
+    }
+    
+    /// Get a user's active DCA schedules
+    pub fn get_user_dcas(env: Env, user: Address) -> Vec<DcaSchedule> {
+        let mut schedules = Vec::new(&env);
+        for schedule_id in Self::id_index(&env, &DataKey::UserSchedules(user)).iter() {
+            if let Some(schedule) = env.storage().persistent().get(&DataKey::Schedule(schedule_id)) {
+                schedules.push_back(schedule);
+            }
+        }
+        schedules
+    }
+
+    // Internal helper functions
+    
+    fn validate_swap_request(
+        env: &Env,
+        request: &SwapRequest,
+    ) -> Result<(), SwapError> {
+        let config = Self::get_config(env.clone())?;
+        
+        // Check minimum amount
+        if request.route.total_amount_in < config.min_swap_amount {
+            return Err(SwapError::InvalidAmount);
+        }
+        
+        // Check slippage tolerance
+        if request.max_slippage > config.max_slippage {
+            return Err(SwapError::InvalidSlippage);
+        }
+        
+        Self::validate_route(&request.route)
+    }
+    
+    fn validate_route(route: &SwapRoute) -> Result<(), SwapError> {
+        // Validate route has steps
+        if route.steps.is_empty() {
+            return Err(SwapError::InvalidRoute);
+        }
+        
+        for i in 1..route.steps.len() {
+            let prev = route.steps.get_unchecked(i - 1);
+            let next = route.steps.get_unchecked(i);
+            
+            // Validate each hop starts with the token the previous hop produced
+            if prev.token_out != next.token_in {
+                return Err(SwapError::InvalidRoute);
+            }
+            
+            // A pool may only be used once, since hops are priced against its current reserves
+            for j in 0..i {
+                if route.steps.get_unchecked(j).pool_id == next.pool_id {
+                    return Err(SwapError::InvalidRoute);
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Load the pool a step trades against, check it matches the step's tokens and
+    /// scale its fee to `fee_rate` relative to BASE_FEE_RATE
+    fn load_step_pool(env: &Env, config: &Config, step: &SwapStep, fee_rate: u32) -> Result<Pool, SwapError> {
+        let mut pool = Self::read_pool(env, &step.pool_id)?;
+        
+        if !pool.enabled {
+            return Err(SwapError::PoolNotFound);
+        }
+        
+        // Validate pool tokens match step
+        let valid_pair = (pool.token_a == step.token_in && pool.token_b == step.token_out) ||
+                        (pool.token_b == step.token_in && pool.token_a == step.token_out);
+        
+        if !valid_pair {
+            return Err(SwapError::InvalidRoute);
+        }
+        
+        let reserves = Self::get_reserves(env, config, &pool.id)?;
+        pool.reserve_a = reserves.reserve_a;
+        pool.reserve_b = reserves.reserve_b;
+        pool.fee = pool.fee * fee_rate / BASE_FEE_RATE;
+        
+        Ok(pool)
+    }
+    
+    /// Registry entry as stored, without refreshing reserves
+    fn read_pool(env: &Env, pool_id: &String) -> Result<Pool, SwapError> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Pool(pool_id.clone()))
+            .ok_or(SwapError::PoolNotFound)
+    }
+    
+    fn get_reserves(env: &Env, config: &Config, pool_id: &String) -> Result<PoolReserves, SwapError> {
+        LiquidityClient::new(env, &config.liquidity_contract)
+            .try_get_reserves(pool_id)
+            .map_err(|_| SwapError::PoolNotFound)?
+            .map_err(|_| SwapError::PoolNotFound)
+    }
+    
+    fn sum(values: &Vec<i128>) -> i128 {
+        let mut total = 0i128;
+        for value in values.iter() {
+            total += value;
+        }
+        total
+    }
+    
+    /// Amounts along an exact-input route: amounts[i] enters hop i, the last entry is the output
+    fn price_exact_in(
+        env: &Env,
+        config: &Config,
+        steps: &Vec<SwapStep>,
+        amount_in: i128,
+        fee_rate: u32,
+    ) -> Result<(Vec<i128>, Vec<i128>), SwapError> {
+        if amount_in <= 0 {
+            return Err(SwapError::InvalidAmount);
+        }
+        
+        let mut amounts = Vec::new(env);
+        let mut fees = Vec::new(env);
+        amounts.push_back(amount_in);
+        
+        let mut amount = amount_in;
+        for step in steps.iter() {
+            let pool = Self::load_step_pool(env, config, &step, fee_rate)?;
+            let (amount_out, fee) = Self::calculate_hop(&pool, &step.token_in, amount)?;
+            amounts.push_back(amount_out);
+            fees.push_back(fee);
+            amount = amount_out;
+        }
+        
+        Ok((amounts, fees))
+    }
+    
+    /// Amounts along an exact-output route, computed backwards from `amount_out`
+    fn price_exact_out(
+        env: &Env,
+        config: &Config,
+        steps: &Vec<SwapStep>,
+        amount_out: i128,
+        fee_rate: u32,
+    ) -> Result<(Vec<i128>, Vec<i128>), SwapError> {
+        let mut amounts = Vec::new(env);
+        let mut fees = Vec::new(env);
+        amounts.push_front(amount_out);
+        
+        let mut required = amount_out;
+        for i in (0..steps.len()).rev() {
+            let step = steps.get_unchecked(i);
+            let pool = Self::load_step_pool(env, config, &step, fee_rate)?;
+            let (amount_in, fee) = Self::calculate_hop_input(&pool, &step.token_in, required)?;
+            amounts.push_front(amount_in);
+            fees.push_front(fee);
+            required = amount_in;
+        }
+        
+        Ok((amounts, fees))
+    }
+    
+    /// Fee rate the fee manager charges `user`, in basis points of a standard-tier trade.
+    /// Pool fees are scaled by it relative to BASE_FEE_RATE, so tiers only ever discount.
+    /// Without a reachable fee manager everyone pays the base rate.
+    fn user_fee_rate(env: &Env, user: &Address) -> u32 {
+        let Some(fee_manager) = env.storage().persistent().get::<_, Address>(&DataKey::FeeManager) else {
+            return BASE_FEE_RATE;
+        };
+        
+        match FeeManagerClient::new(env, &fee_manager).try_calculate_swap_fee(user, &10000) {
+            Ok(Ok(fee)) => fee.clamp(0, BASE_FEE_RATE as i128) as u32,
+            _ => BASE_FEE_RATE,
+        }
+    }
+    
+    /// Credit a trade's volume and fees towards the user's fee tier, valued in XLM stroops
+    /// like the tier thresholds, and record the fees for revenue distribution. Best effort:
+    /// trades the oracle cannot value are not reported, and a failing fee manager never fails the trade.
+    fn report_swap_volume(env: &Env, user: &Address, steps: &Vec<SwapStep>, amounts: &Vec<i128>, fees: &Vec<i128>) {
+        let Some(fee_manager) = env.storage().persistent().get::<_, Address>(&DataKey::FeeManager) else {
+            return;
+        };
+        let Some(oracle) = env.storage().persistent().get::<_, Address>(&DataKey::Oracle) else {
+            return;
+        };
+        
+        // Each hop's fee is charged in that hop's input token
+        let xlm = Token {
+            address: None,
+            symbol: String::from_str(env, "XLM"),
+            decimals: XLM_DECIMALS,
+        };
+        let mut fees_paid = 0i128;
+        for i in 0..steps.len() {
+            let Ok(fee) = Self::oracle_amount_out(env, &oracle, &steps.get_unchecked(i).token_in, &xlm, fees.get_unchecked(i), false) else {
+                return;
+            };
+            fees_paid += fee;
+        }
+        let Ok(volume) = Self::oracle_amount_out(env, &oracle, &steps.get_unchecked(0).token_in, &xlm, amounts.first_unchecked(), false) else {
+            return;
+        };
+        
+        let client = FeeManagerClient::new(env, &fee_manager);
+        let _ = client.try_update_user_volume(user, &volume, &fees_paid);
+        let _ = client.try_collect_and_distribute_fees(&env.current_contract_address(), &fees_paid, &String::from_str(env, "swap"));
+    }
+    
+    /// One hop's output at the pool's marginal price, fees included
+    fn spot_hop_out(pool: &Pool, token_in: &Token, amount_in: i128) -> Result<i128, SwapError> {
+        let (reserve_in, reserve_out) = if pool.token_a == *token_in {
+            (pool.reserve_a, pool.reserve_b)
+        } else {
+            (pool.reserve_b, pool.reserve_a)
+        };
+        
+        if reserve_in == 0 {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        
+        let fee = (amount_in * pool.fee as i128) / 10000;
+        Ok((amount_in - fee) * reserve_out / reserve_in)
+    }
+    
+    /// Break priced route amounts down per hop, against the reserves they were priced on
+    fn build_quote(
+        env: &Env,
+        config: &Config,
+        steps: &Vec<SwapStep>,
+        amounts: &Vec<i128>,
+        fees: &Vec<i128>,
+        fee_rate: u32,
+        slippage_tolerance: u32,
+    ) -> Result<SwapQuote, SwapError> {
+        if slippage_tolerance > config.max_slippage {
+            return Err(SwapError::InvalidSlippage);
+        }
+        
+        let mut hops = Vec::new(env);
+        let amount_in = amounts.first_unchecked();
+        let amount_out = amounts.last_unchecked();
+        let mut spot_amount_out = amount_in;
+        
+        for i in 0..steps.len() {
+            let step = steps.get_unchecked(i);
+            let pool = Self::load_step_pool(env, config, &step, fee_rate)?;
+            let (reserve_in, reserve_out) = if pool.token_a == step.token_in {
+                (pool.reserve_a, pool.reserve_b)
+            } else {
+                (pool.reserve_b, pool.reserve_a)
+            };
+            
+            let hop_in = amounts.get_unchecked(i);
+            let hop_out = amounts.get_unchecked(i + 1);
+            
+            // Quote only what the swap itself would let through
+            Self::check_price_guard(env, &step, hop_in, hop_out)?;
+            
+            // What the hop, and the route so far, would pay at marginal prices
+            let ideal_out = Self::spot_hop_out(&pool, &step.token_in, hop_in)?;
+            spot_amount_out = Self::spot_hop_out(&pool, &step.token_in, spot_amount_out)?;
+            
+            hops.push_back(HopQuote {
+                pool_id: step.pool_id.clone(),
+                token_in: step.token_in.clone(),
+                token_out: step.token_out.clone(),
+                amount_in: hop_in,
+                amount_out: hop_out,
+                fee: fees.get_unchecked(i),
+                spot_price_before: reserve_out * PRICE_PRECISION / reserve_in,
+                spot_price_after: (reserve_out - hop_out) * PRICE_PRECISION / (reserve_in + hop_in),
+                price_impact_bps: Self::calculate_slippage(ideal_out, hop_out)?,
+            });
+        }
+        
+        let tolerance = slippage_tolerance as i128;
+        Ok(SwapQuote {
+            hops,
+            amount_in,
+            amount_out,
+            total_fees: Self::sum(fees),
+            price_impact_bps: Self::calculate_slippage(spot_amount_out, amount_out)?,
+            min_amount_out: amount_out * (10000 - tolerance) / 10000,
+            max_amount_in: amount_in * (10000 + tolerance) / 10000,
+            quoted_at: env.ledger().timestamp(),
+        })
+    }
+    
+    /// Move the route input from `payer` into the liquidity contract and settle every hop there.
+    /// Intermediate outputs stay in the liquidity contract; the final output goes to `recipient`.
+    fn settle_route(
+        env: &Env,
+        config: &Config,
+        steps: &Vec<SwapStep>,
+        amounts: &Vec<i128>,
+        fees: &Vec<i128>,
+        payer: &Address,
+        recipient: &Address,
+    ) -> Result<(), SwapError> {
+        let liquidity = LiquidityClient::new(env, &config.liquidity_contract);
+        let first_step = steps.first().ok_or(SwapError::InvalidRoute)?;
+        
+        // Trip the circuit breaker before any tokens move
+        for i in 0..steps.len() {
+            Self::check_price_guard(env, &steps.get_unchecked(i), amounts.get_unchecked(i), amounts.get_unchecked(i + 1))?;
+        }
+        let token_in = first_step.token_in.address.clone().ok_or(SwapError::TokenNotSupported)?;
+        
+        TokenClient::new(env, &token_in).transfer(payer, &config.liquidity_contract, &amounts.first_unchecked());
+        
+        let hop_count = steps.len();
+        for i in 0..hop_count {
+            let step = steps.get_unchecked(i);
+            let hop_token_in = step.token_in.address.clone().ok_or(SwapError::TokenNotSupported)?;
+            let to = if i + 1 == hop_count {
+                recipient.clone()
+            } else {
+                config.liquidity_contract.clone()
+            };
+            
+            liquidity
+                .try_swap(
+                    &step.pool_id,
+                    &hop_token_in,
+                    &amounts.get_unchecked(i),
+                    &fees.get_unchecked(i),
+                    &amounts.get_unchecked(i + 1),
+                    &to,
+                )
+                .map_err(|_| SwapError::SwapFailed)?
+                .map_err(|_| SwapError::SwapFailed)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Reject a hop whose execution price deviates from the oracle by more than the pool's bound
+    fn check_price_guard(
+        env: &Env,
+        step: &SwapStep,
+        amount_in: i128,
+        amount_out: i128,
+    ) -> Result<(), SwapError> {
+        let Some(guard) = Self::get_price_guard(env.clone(), step.pool_id.clone()) else {
+            return Ok(());
+        };
+        
+        // A guarded pool fails closed when it cannot be priced
+        let oracle: Address = env.storage().persistent()
+            .get(&DataKey::Oracle)
+            .ok_or(SwapError::OracleUnavailable)?;
+        let expected_out = Self::oracle_amount_out(env, &oracle, &step.token_in, &step.token_out, amount_in, guard.use_twap)?;
+        if expected_out <= 0 {
+            return Err(SwapError::OracleUnavailable);
+        }
+        
+        let deviation = (expected_out - amount_out).abs().saturating_mul(10000) / expected_out;
+        if deviation > guard.max_deviation_bps as i128 {
+            return Err(SwapError::PriceDeviationExceeded);
+        }
+        
+        Ok(())
+    }
+    
+    /// Output the oracle prices imply for `amount_in` of `token_in`
+    fn oracle_amount_out(
+        env: &Env,
+        oracle: &Address,
+        token_in: &Token,
+        token_out: &Token,
+        amount_in: i128,
+        use_twap: bool,
+    ) -> Result<i128, SwapError> {
+        let price_in = Self::oracle_price(env, oracle, token_in, use_twap)?;
+        let price_out = Self::oracle_price(env, oracle, token_out, use_twap)?;
+        
+        let unit_in = 10i128.checked_pow(token_in.decimals).ok_or(SwapError::OracleUnavailable)?;
+        let unit_out = 10i128.checked_pow(token_out.decimals).ok_or(SwapError::OracleUnavailable)?;
+        amount_in
+            .checked_mul(price_in)
+            .and_then(|value| value.checked_div(unit_in))
+            .and_then(|value| value.checked_mul(unit_out))
+            .and_then(|value| value.checked_div(price_out))
+            .ok_or(SwapError::OracleUnavailable)
+    }
+    
+    /// Oracle USD price of a token, spot or TWAP
+    fn oracle_price(env: &Env, oracle: &Address, token: &Token, use_twap: bool) -> Result<i128, SwapError> {
+        let client = OracleClient::new(env, oracle);
+        let price = if use_twap {
+            client
+                .try_get_twap_price(&token.symbol)
+                .map_err(|_| SwapError::OracleUnavailable)?
+                .map_err(|_| SwapError::OracleUnavailable)?
+        } else {
+            client
+                .try_get_price(&token.symbol)
+                .map_err(|_| SwapError::OracleUnavailable)?
+                .map_err(|_| SwapError::OracleUnavailable)?
+                .price
+        };
+        
+        if price <= 0 {
+            return Err(SwapError::OracleUnavailable);
+        }
+        Ok(price)
+    }
+    
+    /// Output and fee for one hop; the fee is taken from the input before it reaches the pool
+    fn calculate_hop(
+        pool: &Pool,
+        token_in: &Token,
+        amount_in: i128,
+    ) -> Result<(i128, i128), SwapError> {
+        let fee = (amount_in * pool.fee as i128) / 10000;
+        let amount_out = Self::calculate_amm_output(amount_in - fee, pool, token_in)?;
+        
+        Ok((amount_out, fee))
+    }
+    
+    /// Gross input and fee needed for a hop to produce `amount_out`
+    fn calculate_hop_input(
+        pool: &Pool,
+        token_in: &Token,
+        amount_out: i128,
+    ) -> Result<(i128, i128), SwapError> {
+        let amount_in_after_fee = Self::calculate_amm_input(amount_out, pool, token_in)?;
+        
+        // Smallest gross input whose post-fee amount still covers amount_in_after_fee
+        let fee_denominator = 10000 - pool.fee as i128;
+        if fee_denominator <= 0 {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        let mut amount_in = (amount_in_after_fee * 10000 + fee_denominator - 1) / fee_denominator;
+        
+        // The fee is rounded down, so the ceiling can overshoot by a unit
+        while amount_in > 1 && (amount_in - 1) - ((amount_in - 1) * pool.fee as i128) / 10000 >= amount_in_after_fee {
+            amount_in -= 1;
+        }
+        let fee = (amount_in * pool.fee as i128) / 10000;
+        
+        Ok((amount_in, fee))
+    }
+    
+    /// Depth-first search over simple paths, keeping the path with the best output
+    #[allow(clippy::too_many_arguments)]
+    fn search_routes(
+        pools: &Vec<Pool>,
+        current: &Token,
+        target: &Token,
+        amount_in: i128,
+        hops_left: u32,
+        path: &mut Vec<SwapStep>,
+        best_steps: &mut Vec<SwapStep>,
+        best_amount_out: &mut i128,
+    ) {
+        for pool in pools.iter() {
+            let next = if pool.token_a == *current {
+                pool.token_b.clone()
+            } else if pool.token_b == *current {
+                pool.token_a.clone()
+            } else {
+                continue;
+            };
+            
+            // Never revisit a pool or a token already on the path
+            let mut visited = false;
+            for step in path.iter() {
+                if step.pool_id == pool.id || step.token_in == next {
+                    visited = true;
+                    break;
+                }
+            }
+            if visited {
+                continue;
+            }
+            
+            let (amount_out, fee) = match Self::calculate_hop(&pool, current, amount_in) {
+                Ok(result) => result,
+                Err(_) => continue,
+            };
+            
+            path.push_back(SwapStep {
+                token_in: current.clone(),
+                token_out: next.clone(),
+                pool_id: pool.id.clone(),
+                amount_in,
+                amount_out,
+                fee,
+            });
+            
+            if next == *target {
+                if amount_out > *best_amount_out {
+                    *best_amount_out = amount_out;
+                    *best_steps = path.clone();
+                }
+            } else if hops_left > 1 {
+                Self::search_routes(
+                    pools,
+                    &next,
+                    target,
+                    amount_out,
+                    hops_left - 1,
+                    path,
+                    best_steps,
+                    best_amount_out,
+                );
+            }
+            
+            path.pop_back();
+        }
+    }
+    
+    fn calculate_amm_output(
+        amount_in: i128,
+        pool: &Pool,
+        token_in: &Token,
+    ) -> Result<i128, SwapError> {
+        // Simple AMM formula: x * y = k
+        let (reserve_in, reserve_out) = if pool.token_a == *token_in {
+            (pool.reserve_a, pool.reserve_b)
+        } else {
+            (pool.reserve_b, pool.reserve_a)
+        };
+        
+        if reserve_in == 0 || reserve_out == 0 {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        
+        // amount_out = (amount_in * reserve_out) / (reserve_in + amount_in)
+        let amount_out = (amount_in * reserve_out) / (reserve_in + amount_in);
+        
+        if amount_out <= 0 {
+            return Err(SwapError::InsufficientLiquidity);
         }
         
         Ok(amount_out)
     }
     
+    fn calculate_amm_input(
+        amount_out: i128,
+        pool: &Pool,
+        token_in: &Token,
+    ) -> Result<i128, SwapError> {
+        let (reserve_in, reserve_out) = if pool.token_a == *token_in {
+            (pool.reserve_a, pool.reserve_b)
+        } else {
+            (pool.reserve_b, pool.reserve_a)
+        };
+        
+        if reserve_in == 0 || amount_out >= reserve_out {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        
+        // amount_in = (reserve_in * amount_out) / (reserve_out - amount_out), rounded up
+        let denominator = reserve_out - amount_out;
+        Ok((reserve_in * amount_out + denominator - 1) / denominator)
+    }
+    
     fn calculate_slippage(
         expected: i128,
         actual: i128,
@@ -427,6 +2106,107 @@ impl SwapContract {
         Ok(slippage as u32)
     }
     
+    fn get_pool_ids(env: &Env) -> Vec<String> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PoolIds)
+            .unwrap_or(Vec::new(env))
+    }
+    
+    /// Token metadata as reported by the token contract itself
+    fn read_token(env: &Env, address: &Address) -> Result<Token, SwapError> {
+        let client = TokenClient::new(env, address);
+        let symbol = client
+            .try_symbol()
+            .map_err(|_| SwapError::TokenNotSupported)?
+            .map_err(|_| SwapError::TokenNotSupported)?;
+        let decimals = client
+            .try_decimals()
+            .map_err(|_| SwapError::TokenNotSupported)?
+            .map_err(|_| SwapError::TokenNotSupported)?;
+        
+        Ok(Token {
+            address: Some(address.clone()),
+            symbol,
+            decimals,
+        })
+    }
+    
+    /// Canonical pair ordering: by token address, then symbol
+    fn sort_tokens(token_a: Token, token_b: Token) -> (Token, Token) {
+        let a_first = match token_a.address.cmp(&token_b.address) {
+            Ordering::Equal => token_a.symbol <= token_b.symbol,
+            ordering => ordering == Ordering::Less,
+        };
+        
+        if a_first {
+            (token_a, token_b)
+        } else {
+            (token_b, token_a)
+        }
+    }
+    
+    /// Deterministic pool ID: hex of sha256(token_a || token_b || fee_tier), truncated to 16 bytes
+    fn derive_pool_id(
+        env: &Env,
+        token_a: &Token,
+        token_b: &Token,
+        fee_tier: u32,
+    ) -> Result<String, SwapError> {
+        let address_a = token_a.address.clone().ok_or(SwapError::TokenNotSupported)?;
+        let address_b = token_b.address.clone().ok_or(SwapError::TokenNotSupported)?;
+        
+        let mut preimage = Bytes::new(env);
+        preimage.append(&address_a.to_xdr(env));
+        preimage.append(&address_b.to_xdr(env));
+        preimage.extend_from_array(&fee_tier.to_be_bytes());
+        let digest = env.crypto().sha256(&preimage).to_array();
+        
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        let mut id = [0u8; 32];
+        for i in 0..16 {
+            id[2 * i] = HEX[(digest[i] >> 4) as usize];
+            id[2 * i + 1] = HEX[(digest[i] & 0x0f) as usize];
+        }
+        
+        Ok(String::from_bytes(env, &id))
+    }
+    
+    /// IDs of the enabled pools trading a token pair, in either order
+    fn pair_pool_ids(env: &Env, token_a: Token, token_b: Token) -> Vec<String> {
+        let (first, second) = Self::sort_tokens(token_a, token_b);
+        env.storage()
+            .persistent()
+            .get(&DataKey::PairPools(first, second))
+            .unwrap_or(Vec::new(env))
+    }
+    
+    fn index_pair(env: &Env, pool: &Pool) {
+        let (first, second) = Self::sort_tokens(pool.token_a.clone(), pool.token_b.clone());
+        let key = DataKey::PairPools(first, second);
+        let mut pool_ids: Vec<String> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+        
+        if !pool_ids.contains(&pool.id) {
+            pool_ids.push_back(pool.id.clone());
+            env.storage().persistent().set(&key, &pool_ids);
+        }
+    }
+    
+    fn unindex_pair(env: &Env, pool: &Pool) {
+        let (first, second) = Self::sort_tokens(pool.token_a.clone(), pool.token_b.clone());
+        let key = DataKey::PairPools(first, second);
+        let mut pool_ids: Vec<String> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+        
+        if let Some(index) = pool_ids.first_index_of(&pool.id) {
+            pool_ids.remove(index);
+            if pool_ids.is_empty() {
+                env.storage().persistent().remove(&key);
+            } else {
+                env.storage().persistent().set(&key, &pool_ids);
+            }
+        }
+    }
+    
     fn get_admin(env: &Env) -> Result<Address, SwapError> {
         env.storage()
             .persistent()
@@ -434,6 +2214,60 @@ impl SwapContract {
             .ok_or(SwapError::Unauthorized)
     }
 
+    fn id_index(env: &Env, key: &DataKey) -> Vec<u64> {
+        env.storage().persistent().get(key).unwrap_or(Vec::new(env))
+    }
+    
+    fn load_orders(env: &Env, key: &DataKey) -> Vec<LimitOrder> {
+        let mut orders = Vec::new(env);
+        for order_id in Self::id_index(env, key).iter() {
+            if let Some(order) = env.storage().persistent().get(&DataKey::Order(order_id)) {
+                orders.push_back(order);
+            }
+        }
+        orders
+    }
+    
+    /// Return an order's escrowed input to its owner
+    fn refund_order(env: &Env, order: &LimitOrder) -> Result<(), SwapError> {
+        let token_in = order.token_in.address.clone().ok_or(SwapError::TokenNotSupported)?;
+        TokenClient::new(env, &token_in).transfer(&env.current_contract_address(), &order.owner, &order.amount_in);
+        Ok(())
+    }
+    
+    /// Store a settled order and drop it from the open order indexes
+    fn close_order(env: &Env, order: &LimitOrder) {
+        env.storage().persistent().set(&DataKey::Order(order.id), order);
+        
+        for key in [DataKey::UserOrders(order.owner.clone()), DataKey::PoolOrders(order.pool_id.clone())] {
+            let mut order_ids = Self::id_index(env, &key);
+            if let Some(index) = order_ids.first_index_of(order.id) {
+                order_ids.remove(index);
+            }
+            if order_ids.is_empty() {
+                env.storage().persistent().remove(&key);
+            } else {
+                env.storage().persistent().set(&key, &order_ids);
+            }
+        }
+    }
+
+    /// Store a finished schedule and drop it from its owner's active index
+    fn close_schedule(env: &Env, schedule: &DcaSchedule) {
+        env.storage().persistent().set(&DataKey::Schedule(schedule.id), schedule);
+        
+        let key = DataKey::UserSchedules(schedule.owner.clone());
+        let mut schedule_ids = Self::id_index(env, &key);
+        if let Some(index) = schedule_ids.first_index_of(schedule.id) {
+            schedule_ids.remove(index);
+        }
+        if schedule_ids.is_empty() {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &schedule_ids);
+        }
+    }
+
     fn record_swap_history(
         env: &Env,
         user: &Address,
@@ -455,4 +2289,7 @@ impl SwapContract {
         env.storage().persistent().set(&key, &history);
         Ok(())
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod test;
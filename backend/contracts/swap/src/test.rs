@@ -0,0 +1,872 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::{contract, contractimpl, testutils::{Address as _, Ledger}, token::StellarAssetClient, vec};
+use swave_fee_manager::{
+    DataKey as FeeManagerKey, RevenueDistribution, UltimateFeeManagerContract, UltimateFeeManagerContractClient,
+};
+
+const RESERVE: i128 = 10_000_000_000;       // 1,000 tokens at 7 decimals
+const USD: i128 = 100_000_000;              // $1 at 8 decimals
+
+/// Oracle returning prices set by the test, keyed by token symbol
+#[contract]
+pub struct MockOracle;
+
+#[contractimpl]
+impl MockOracle {
+    pub fn set_price(env: Env, asset: String, price: i128, twap_price: i128) {
+        env.storage().instance().set(&asset, &(price, twap_price));
+    }
+
+    pub fn get_price(env: Env, asset: String) -> AggregatedPrice {
+        let (price, twap_price): (i128, i128) = env.storage().instance().get(&asset).unwrap();
+        AggregatedPrice {
+            asset,
+            price,
+            timestamp: env.ledger().timestamp(),
+            sources: Vec::new(&env),
+            confidence_score: 100,
+            twap_price,
+            deviation: 0,
+        }
+    }
+
+    pub fn get_twap_price(env: Env, asset: String) -> i128 {
+        let (_, twap_price): (i128, i128) = env.storage().instance().get(&asset).unwrap();
+        twap_price
+    }
+}
+
+/// Liquidity contract custodying pool reserves, with the settlement checks of the real one
+#[contract]
+pub struct MockLiquidity;
+
+#[contractimpl]
+impl MockLiquidity {
+    pub fn create_pool(env: Env, _caller: Address, pool_id: String, token_a: Address, token_b: Address) {
+        assert!(!env.storage().instance().has(&pool_id));
+        let reserves = PoolReserves { token_a, token_b, reserve_a: 0, reserve_b: 0 };
+        env.storage().instance().set(&pool_id, &reserves);
+    }
+
+    pub fn get_reserves(env: Env, pool_id: String) -> PoolReserves {
+        env.storage().instance().get(&pool_id).unwrap()
+    }
+
+    /// Add reserves to a pool; the test mints the tokens to this contract
+    pub fn seed(env: Env, pool_id: String, amount_a: i128, amount_b: i128) {
+        let mut pool = Self::get_reserves(env.clone(), pool_id.clone());
+        pool.reserve_a += amount_a;
+        pool.reserve_b += amount_b;
+        Self::track(&env, &pool.token_a, amount_a);
+        Self::track(&env, &pool.token_b, amount_b);
+        env.storage().instance().set(&pool_id, &pool);
+    }
+
+    pub fn swap(env: Env, pool_id: String, token_in: Address, amount_in: i128, fee: i128, amount_out: i128, to: Address) {
+        let mut pool = Self::get_reserves(env.clone(), pool_id.clone());
+        let a_to_b = token_in == pool.token_a;
+        let (reserve_in, reserve_out, token_out) = if a_to_b {
+            (pool.reserve_a, pool.reserve_b, pool.token_b.clone())
+        } else {
+            (pool.reserve_b, pool.reserve_a, pool.token_a.clone())
+        };
+        assert!((reserve_in + amount_in - fee) * (reserve_out - amount_out) >= reserve_in * reserve_out);
+
+        if a_to_b {
+            pool.reserve_a += amount_in;
+            pool.reserve_b -= amount_out;
+        } else {
+            pool.reserve_b += amount_in;
+            pool.reserve_a -= amount_out;
+        }
+        env.storage().instance().set(&pool_id, &pool);
+        Self::track(&env, &token_in, amount_in);
+        Self::track(&env, &token_out, -amount_out);
+
+        // The input must have arrived before the hop settles
+        let contract = env.current_contract_address();
+        assert!(TokenClient::new(&env, &token_in).balance(&contract) >= Self::tracked(env.clone(), token_in));
+        if to != contract {
+            TokenClient::new(&env, &token_out).transfer(&contract, &to, &amount_out);
+        }
+    }
+
+    /// Reserves of a token summed over all pools
+    pub fn tracked(env: Env, token: Address) -> i128 {
+        env.storage().instance().get(&token).unwrap_or(0)
+    }
+
+    fn track(env: &Env, token: &Address, amount: i128) {
+        let tracked = Self::tracked(env.clone(), token.clone());
+        env.storage().instance().set(token, &(tracked + amount));
+    }
+}
+
+/// Fee manager charging a fixed rate and recording reported volume
+#[contract]
+pub struct MockFeeManager;
+
+#[contractimpl]
+impl MockFeeManager {
+    pub fn calculate_swap_fee(_env: Env, _user: Address, amount: i128) -> i128 {
+        amount * 15 / 10000
+    }
+
+    pub fn update_user_volume(env: Env, user: Address, volume: i128, fees_paid: i128) {
+        let (total_volume, total_fees): (i128, i128) = env.storage().instance().get(&user).unwrap_or((0, 0));
+        env.storage().instance().set(&user, &(total_volume + volume, total_fees + fees_paid));
+    }
+
+    pub fn get_volume(env: Env, user: Address) -> (i128, i128) {
+        env.storage().instance().get(&user).unwrap_or((0, 0))
+    }
+}
+
+struct Setup {
+    env: Env,
+    admin: Address,
+    contract: SwapContractClient<'static>,
+    liquidity: MockLiquidityClient<'static>,
+    token_a: Token,
+    token_b: Token,
+    token_c: Token,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.cost_estimate().budget().reset_unlimited();
+
+        let admin = Address::generate(&env);
+        let liquidity = MockLiquidityClient::new(&env, &env.register(MockLiquidity, ()));
+        let contract = SwapContractClient::new(&env, &env.register(SwapContract, ()));
+        contract.initialize(&admin, &liquidity.address);
+
+        let token_a = Self::new_token(&env, &admin);
+        let token_b = Self::new_token(&env, &admin);
+        let token_c = Self::new_token(&env, &admin);
+
+        Setup { env, admin, contract, liquidity, token_a, token_b, token_c }
+    }
+
+    fn new_token(env: &Env, admin: &Address) -> Token {
+        let address = env.register_stellar_asset_contract_v2(admin.clone()).address();
+        let client = TokenClient::new(env, &address);
+        Token {
+            address: Some(address),
+            symbol: client.symbol(),
+            decimals: client.decimals(),
+        }
+    }
+
+    /// Register a 30 bps pool and fund its reserves in the liquidity contract
+    fn seed(&self, id: &str, token_a: &Token, token_b: &Token) -> String {
+        self.seed_with(id, token_a, token_b, RESERVE)
+    }
+
+    fn seed_with(&self, id: &str, token_a: &Token, token_b: &Token, reserve: i128) -> String {
+        let pool_id = String::from_str(&self.env, id);
+        let (address_a, address_b) = (token_a.address.clone().unwrap(), token_b.address.clone().unwrap());
+        self.liquidity.create_pool(&self.contract.address, &pool_id, &address_a, &address_b);
+        self.liquidity.seed(&pool_id, &reserve, &reserve);
+        self.mint(token_a, &self.liquidity.address, reserve);
+        self.mint(token_b, &self.liquidity.address, reserve);
+
+        let pool = Pool {
+            id: pool_id.clone(),
+            token_a: token_a.clone(),
+            token_b: token_b.clone(),
+            reserve_a: 0,
+            reserve_b: 0,
+            fee: 30,
+            enabled: true,
+        };
+        self.contract.add_pool(&self.admin, &pool);
+        pool_id
+    }
+
+    fn mint(&self, token: &Token, to: &Address, amount: i128) {
+        StellarAssetClient::new(&self.env, token.address.as_ref().unwrap()).mint(to, &amount);
+    }
+
+    fn set_time(&self, timestamp: u64) {
+        self.env.ledger().with_mut(|ledger| ledger.timestamp = timestamp);
+    }
+
+    fn balance(&self, token: &Token, holder: &Address) -> i128 {
+        TokenClient::new(&self.env, token.address.as_ref().unwrap()).balance(holder)
+    }
+
+    fn trader(&self, token: &Token, amount: i128) -> Address {
+        let trader = Address::generate(&self.env);
+        self.mint(token, &trader, amount);
+        trader
+    }
+
+    fn step(&self, pool_id: &String, token_in: &Token, token_out: &Token, amount_in: i128) -> SwapStep {
+        SwapStep {
+            token_in: token_in.clone(),
+            token_out: token_out.clone(),
+            pool_id: pool_id.clone(),
+            amount_in,
+            amount_out: 0,
+            fee: 0,
+        }
+    }
+
+    fn route(&self, steps: Vec<SwapStep>, amount_in: i128, amount_out: i128) -> SwapRoute {
+        SwapRoute {
+            steps,
+            total_amount_in: amount_in,
+            total_amount_out: amount_out,
+            total_fees: 0,
+            expected_slippage: 0,
+            expires_at: u64::MAX,
+        }
+    }
+
+    fn request(&self, user: &Address, route: SwapRoute, min_amount_out: i128) -> SwapRequest {
+        SwapRequest {
+            user: user.clone(),
+            route,
+            max_slippage: 100,
+            min_amount_out,
+            deadline: u64::MAX,
+        }
+    }
+
+    /// Price every token through a mock oracle, and XLM at $0.10; test assets all share one symbol
+    fn set_oracle(&self, price: i128, twap_price: i128) -> MockOracleClient<'static> {
+        let oracle = MockOracleClient::new(&self.env, &self.env.register(MockOracle, ()));
+        oracle.set_price(&self.token_a.symbol, &price, &twap_price);
+        oracle.set_price(&String::from_str(&self.env, "XLM"), &(USD / 10), &(USD / 10));
+        self.contract.set_oracle(&self.admin, &oracle.address);
+        oracle
+    }
+
+    /// Tracked reserves match custody and nothing is left in the swap contract
+    fn assert_settled(&self) {
+        for token in [&self.token_a, &self.token_b, &self.token_c] {
+            let address = token.address.as_ref().unwrap();
+            assert_eq!(self.liquidity.tracked(address), self.balance(token, &self.liquidity.address));
+            assert_eq!(self.balance(token, &self.contract.address), 0);
+        }
+    }
+}
+
+/// Constant-product output of one hop with the fee taken from the input
+fn hop_out(amount_in: i128, fee_bps: i128, reserve_in: i128, reserve_out: i128) -> i128 {
+    let net = amount_in - amount_in * fee_bps / 10000;
+    net * reserve_out / (reserve_in + net)
+}
+
+#[test]
+fn test_initialize_twice() {
+    let setup = Setup::new();
+
+    assert_eq!(
+        setup.contract.try_initialize(&setup.admin, &setup.liquidity.address),
+        Err(Ok(SwapError::AlreadyInitialized))
+    );
+}
+
+#[test]
+fn test_swap_settles_through_custody() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let amount_in = 100_000_000;
+    let user = setup.trader(&setup.token_a, amount_in);
+    let fee = amount_in * 30 / 10000;
+    let expected_out = hop_out(amount_in, 30, RESERVE, RESERVE);
+
+    let steps = vec![&setup.env, setup.step(&pool_ab, &setup.token_a, &setup.token_b, amount_in)];
+    let result = setup.contract.execute_swap(&setup.request(&user, setup.route(steps, amount_in, expected_out), expected_out));
+    assert_eq!((result.amount_out, result.fees_paid), (expected_out, fee));
+    assert_eq!(setup.balance(&setup.token_a, &user), 0);
+    assert_eq!(setup.balance(&setup.token_b, &user), expected_out);
+
+    // The fee stays in the pool for its LPs
+    let pool = setup.contract.get_pool(&pool_ab);
+    assert_eq!((pool.reserve_a, pool.reserve_b), (RESERVE + amount_in, RESERVE - expected_out));
+    setup.assert_settled();
+}
+
+#[test]
+fn test_swap_without_funds_fails() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let amount_in = 100_000_000;
+    let user = setup.trader(&setup.token_a, amount_in - 1);
+
+    let steps = vec![&setup.env, setup.step(&pool_ab, &setup.token_a, &setup.token_b, amount_in)];
+    assert!(setup.contract.try_execute_swap(&setup.request(&user, setup.route(steps, amount_in, 0), 0)).is_err());
+    assert_eq!(setup.balance(&setup.token_a, &user), amount_in - 1);
+    assert_eq!(setup.contract.get_pool(&pool_ab).reserve_a, RESERVE);
+    setup.assert_settled();
+}
+
+#[test]
+fn test_multi_hop_consumes_previous_output() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let pool_bc = setup.seed("B_C", &setup.token_b, &setup.token_c);
+    let amount_in = 100_000_000;
+    let user = setup.trader(&setup.token_a, amount_in);
+
+    // The second hop is priced on the first hop's output, whatever the step says
+    let middle = hop_out(amount_in, 30, RESERVE, RESERVE);
+    let expected_out = hop_out(middle, 30, RESERVE, RESERVE);
+    let steps = vec![
+        &setup.env,
+        setup.step(&pool_ab, &setup.token_a, &setup.token_b, amount_in),
+        setup.step(&pool_bc, &setup.token_b, &setup.token_c, 1),
+    ];
+    let route = setup.route(steps, amount_in, expected_out);
+
+    assert_eq!(
+        setup.contract.try_execute_swap(&setup.request(&user, route.clone(), expected_out + 1)),
+        Err(Ok(SwapError::SlippageExceeded))
+    );
+    let result = setup.contract.execute_swap(&setup.request(&user, route, expected_out));
+    assert_eq!(result.amount_out, expected_out);
+    assert_eq!(result.fees_paid, amount_in * 30 / 10000 + middle * 30 / 10000);
+    assert_eq!(setup.balance(&setup.token_a, &user), 0);
+    assert_eq!(setup.balance(&setup.token_b, &user), 0);
+    assert_eq!(setup.balance(&setup.token_c, &user), expected_out);
+    setup.assert_settled();
+}
+
+#[test]
+fn test_route_rejects_broken_chains() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let pool_bc = setup.seed("B_C", &setup.token_b, &setup.token_c);
+    let amount_in = 100_000_000;
+    let user = setup.trader(&setup.token_a, amount_in);
+
+    // Each hop must start with the previous hop's output
+    let steps = vec![
+        &setup.env,
+        setup.step(&pool_ab, &setup.token_a, &setup.token_b, amount_in),
+        setup.step(&pool_bc, &setup.token_c, &setup.token_b, amount_in),
+    ];
+    assert_eq!(
+        setup.contract.try_execute_swap(&setup.request(&user, setup.route(steps, amount_in, 1), 1)),
+        Err(Ok(SwapError::InvalidRoute))
+    );
+    assert_eq!(setup.balance(&setup.token_a, &user), amount_in);
+}
+
+#[test]
+fn test_best_route_prefers_deeper_path() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let pool_bc = setup.seed("B_C", &setup.token_b, &setup.token_c);
+    let pool_ac = setup.seed_with("A_C", &setup.token_a, &setup.token_c, RESERVE / 100);
+    let amount_in = 100_000_000;
+
+    // Without route bases only the pair's own pools are searched
+    let direct = setup.contract.get_best_route(&setup.token_a, &setup.token_c, &amount_in, &3);
+    assert_eq!(direct.steps.len(), 1);
+
+    let too_many = vec![
+        &setup.env,
+        setup.token_a.clone(),
+        setup.token_b.clone(),
+        setup.token_c.clone(),
+        Setup::new_token(&setup.env, &setup.admin),
+    ];
+    assert_eq!(
+        setup.contract.try_set_route_bases(&setup.admin, &too_many),
+        Err(Ok(SwapError::InvalidRoute))
+    );
+    setup.contract.set_route_bases(&setup.admin, &vec![&setup.env, setup.token_b.clone()]);
+
+    // The shallow direct pool loses to two hops through deep pools
+    let route = setup.contract.get_best_route(&setup.token_a, &setup.token_c, &amount_in, &3);
+    let expected_out = hop_out(hop_out(amount_in, 30, RESERVE, RESERVE), 30, RESERVE, RESERVE);
+    assert_eq!(route.steps.len(), 2);
+    assert_eq!(route.steps.get_unchecked(0).pool_id, pool_ab);
+    assert_eq!(route.steps.get_unchecked(1).pool_id, pool_bc);
+    assert_eq!(route.total_amount_out, expected_out);
+
+    let direct = setup.contract.get_best_route(&setup.token_a, &setup.token_c, &amount_in, &1);
+    assert_eq!(direct.steps.len(), 1);
+    assert_eq!(direct.steps.get_unchecked(0).pool_id, pool_ac);
+    assert!(direct.total_amount_out < expected_out);
+
+    // The found route executes as quoted
+    let user = setup.trader(&setup.token_a, amount_in);
+    let result = setup.contract.execute_swap(&setup.request(&user, route, expected_out));
+    assert_eq!(result.amount_out, expected_out);
+    setup.assert_settled();
+
+    assert_eq!(
+        setup.contract.try_get_best_route(&setup.token_a, &setup.token_a, &amount_in, &3),
+        Err(Ok(SwapError::InvalidRoute))
+    );
+    assert_eq!(
+        setup.contract.try_get_best_route(&setup.token_a, &setup.token_c, &amount_in, &(MAX_ROUTE_HOPS + 1)),
+        Err(Ok(SwapError::InvalidRoute))
+    );
+}
+
+/// IDs of a list of pools, in order
+fn pool_ids(env: &Env, pools: &Vec<Pool>) -> Vec<String> {
+    let mut ids = Vec::new(env);
+    for pool in pools.iter() {
+        ids.push_back(pool.id);
+    }
+    ids
+}
+
+#[test]
+fn test_list_pools_paginates_in_registration_order() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let pool_bc = setup.seed("B_C", &setup.token_b, &setup.token_c);
+    let pool_ac = setup.seed("A_C", &setup.token_a, &setup.token_c);
+    let env = &setup.env;
+
+    assert_eq!(pool_ids(env, &setup.contract.list_pools(&0, &2)), vec![env, pool_ab.clone(), pool_bc.clone()]);
+    assert_eq!(pool_ids(env, &setup.contract.list_pools(&2, &2)), vec![env, pool_ac.clone()]);
+    assert_eq!(setup.contract.list_pools(&3, &2).len(), 0);
+    assert_eq!(setup.contract.list_pools(&u32::MAX, &u32::MAX).len(), 0);
+    assert_eq!(setup.contract.list_pools(&0, &0).len(), 0);
+
+    // Re-registering a pool keeps its place instead of listing it twice
+    setup.contract.add_pool(&setup.admin, &setup.contract.get_pool(&pool_ab));
+    assert_eq!(pool_ids(env, &setup.contract.list_pools(&0, &10)), vec![env, pool_ab, pool_bc, pool_ac]);
+}
+
+#[test]
+fn test_pair_index_tracks_pool_changes() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let pool_ba = setup.seed("B_A", &setup.token_b, &setup.token_a);
+    let pool_bc = setup.seed("B_C", &setup.token_b, &setup.token_c);
+    let (env, token_a, token_b, token_c) = (&setup.env, &setup.token_a, &setup.token_b, &setup.token_c);
+    let pair = |first: &Token, second: &Token| pool_ids(env, &setup.contract.get_pools_for_pair(first, second));
+
+    // Either token order finds both pools of the pair
+    assert_eq!(pair(token_a, token_b), vec![env, pool_ab.clone(), pool_ba.clone()]);
+    assert_eq!(pair(token_b, token_a), pair(token_a, token_b));
+    assert_eq!(pair(token_a, token_c).len(), 0);
+
+    // Disabled pools leave the pair index but stay registered
+    setup.contract.set_pool_enabled(&setup.admin, &pool_ab, &false);
+    assert_eq!(pair(token_a, token_b), vec![env, pool_ba.clone()]);
+    assert_eq!(setup.contract.list_pools(&0, &10).len(), 3);
+    setup.contract.set_pool_enabled(&setup.admin, &pool_ab, &false);
+    setup.contract.set_pool_enabled(&setup.admin, &pool_ab, &true);
+    assert_eq!(pair(token_a, token_b), vec![env, pool_ba.clone(), pool_ab.clone()]);
+
+    // The registry cannot disagree with the liquidity contract about a pool's tokens
+    let mut moved = setup.contract.get_pool(&pool_bc);
+    moved.token_b = token_a.clone();
+    assert_eq!(setup.contract.try_add_pool(&setup.admin, &moved), Err(Ok(SwapError::InvalidRoute)));
+    assert_eq!(pair(token_b, token_c), vec![env, pool_bc.clone()]);
+
+    // Removal drops the pool from both indexes
+    setup.contract.remove_pool(&setup.admin, &pool_ba);
+    assert_eq!(pair(token_a, token_b), vec![env, pool_ab.clone()]);
+    assert_eq!(pool_ids(env, &setup.contract.list_pools(&0, &10)), vec![env, pool_ab, pool_bc]);
+    assert_eq!(setup.contract.try_get_pool(&pool_ba), Err(Ok(SwapError::PoolNotFound)));
+    assert_eq!(setup.contract.try_remove_pool(&setup.admin, &pool_ba), Err(Ok(SwapError::PoolNotFound)));
+
+    let other = Address::generate(env);
+    assert_eq!(
+        setup.contract.try_set_pool_enabled(&other, &pool_ba, &true),
+        Err(Ok(SwapError::Unauthorized))
+    );
+}
+
+#[test]
+fn test_exact_out_receives_requested_amount() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let pool_bc = setup.seed("B_C", &setup.token_b, &setup.token_c);
+    let funded = 1_000_000_000;
+    let user = setup.trader(&setup.token_a, funded);
+    let steps = vec![
+        &setup.env,
+        setup.step(&pool_ab, &setup.token_a, &setup.token_b, 0),
+        setup.step(&pool_bc, &setup.token_b, &setup.token_c, 0),
+    ];
+    let amount_out = 50_000_000;
+    let route = setup.route(steps, 0, amount_out);
+
+    assert_eq!(
+        setup.contract.try_swap_tokens_for_exact_tokens(&user, &route, &amount_out, &amount_out, &u64::MAX),
+        Err(Ok(SwapError::SlippageExceeded))
+    );
+    let result = setup.contract.swap_tokens_for_exact_tokens(&user, &route, &amount_out, &funded, &u64::MAX);
+    assert_eq!(result.amount_out, amount_out);
+    assert_eq!(setup.balance(&setup.token_c, &user), amount_out);
+    assert_eq!(setup.balance(&setup.token_a, &user), funded - result.amount_in);
+
+    // The input paid buys at least the output at the reserves the swap started from
+    let forward = hop_out(hop_out(result.amount_in, 30, RESERVE, RESERVE), 30, RESERVE, RESERVE);
+    assert!(forward >= amount_out);
+    setup.assert_settled();
+}
+
+#[test]
+fn test_route_rejects_repeated_pools() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let amount_in = 100_000_000;
+    let user = setup.trader(&setup.token_a, amount_in);
+
+    // Every hop is priced on the reserves it started from, so a pool may appear once
+    let steps = vec![
+        &setup.env,
+        setup.step(&pool_ab, &setup.token_a, &setup.token_b, amount_in),
+        setup.step(&pool_ab, &setup.token_b, &setup.token_a, amount_in),
+    ];
+    let route = setup.route(steps, amount_in, 1);
+    assert_eq!(
+        setup.contract.try_execute_swap(&setup.request(&user, route.clone(), 1)),
+        Err(Ok(SwapError::InvalidRoute))
+    );
+    assert_eq!(
+        setup.contract.try_swap_tokens_for_exact_tokens(&user, &route, &1_000_000, &amount_in, &u64::MAX),
+        Err(Ok(SwapError::InvalidRoute))
+    );
+}
+
+#[test]
+fn test_create_pool_registers_and_indexes() {
+    let setup = Setup::new();
+    let (env, token_a, token_b) = (&setup.env, &setup.token_a, &setup.token_b);
+
+    assert_eq!(setup.contract.try_create_pool(token_a, token_b, &25), Err(Ok(SwapError::InvalidFeeTier)));
+    assert_eq!(setup.contract.try_create_pool(token_a, token_a, &30), Err(Ok(SwapError::InvalidRoute)));
+
+    // The ID does not depend on the order the tokens are given in
+    let pool_id = setup.contract.create_pool(token_b, token_a, &30);
+    assert_eq!(
+        setup.contract.try_create_pool(token_a, token_b, &30),
+        Err(Ok(SwapError::PoolAlreadyExists))
+    );
+    let other_tier = setup.contract.create_pool(token_a, token_b, &100);
+    assert_ne!(pool_id, other_tier);
+
+    // The pool is opened in the liquidity contract under the same ID and token order
+    let pool = setup.contract.get_pool(&pool_id);
+    let reserves = setup.liquidity.get_reserves(&pool_id);
+    assert_eq!((pool.reserve_a, pool.reserve_b, pool.fee), (0, 0, 30));
+    assert_eq!((pool.token_a.address, pool.token_b.address), (Some(reserves.token_a), Some(reserves.token_b)));
+    assert_eq!(pool_ids(env, &setup.contract.list_pools(&0, &10)), vec![env, pool_id.clone(), other_tier.clone()]);
+    assert_eq!(pool_ids(env, &setup.contract.get_pools_for_pair(token_a, token_b)), vec![env, pool_id, other_tier]);
+
+    // A pool the liquidity contract already holds is a duplicate; any other failure is not
+    let other = SwapContractClient::new(env, &env.register(SwapContract, ()));
+    other.initialize(&setup.admin, &setup.liquidity.address);
+    assert_eq!(other.try_create_pool(token_a, token_b, &30), Err(Ok(SwapError::PoolAlreadyExists)));
+    let unlinked = SwapContractClient::new(env, &env.register(SwapContract, ()));
+    unlinked.initialize(&setup.admin, &Address::generate(env));
+    assert_eq!(unlinked.try_create_pool(token_a, token_b, &30), Err(Ok(SwapError::SwapFailed)));
+}
+
+#[test]
+fn test_price_guard_trips_on_oracle_deviation() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let amount_in = 100_000_000;
+    let user = setup.trader(&setup.token_a, 2 * amount_in);
+    let steps = vec![&setup.env, setup.step(&pool_ab, &setup.token_a, &setup.token_b, amount_in)];
+    let request = setup.request(&user, setup.route(steps, amount_in, 0), 0);
+    let swap = || setup.contract.try_execute_swap(&request).map(|result| result.unwrap().amount_out);
+
+    let other = Address::generate(&setup.env);
+    assert_eq!(setup.contract.try_set_price_guard(&other, &pool_ab, &200, &false), Err(Ok(SwapError::Unauthorized)));
+    let unknown = String::from_str(&setup.env, "X_Y");
+    assert_eq!(setup.contract.try_set_price_guard(&setup.admin, &unknown, &200, &false), Err(Ok(SwapError::PoolNotFound)));
+    assert_eq!(
+        setup.contract.try_set_price_guard(&setup.admin, &pool_ab, &(MAX_PRICE_DEVIATION + 1), &false),
+        Err(Ok(SwapError::InvalidSlippage))
+    );
+
+    // A guarded pool fails closed without an oracle
+    setup.contract.set_price_guard(&setup.admin, &pool_ab, &200, &false);
+    assert_eq!(swap(), Err(Ok(SwapError::OracleUnavailable)));
+
+    // The 30 bps fee and ~1% price impact put this trade ~1.3% off the oracle price
+    let oracle = setup.set_oracle(USD, 0);
+    setup.contract.set_price_guard(&setup.admin, &pool_ab, &100, &false);
+    assert_eq!(swap(), Err(Ok(SwapError::PriceDeviationExceeded)));
+
+    // A TWAP guard reads the TWAP, not the spot price
+    setup.contract.set_price_guard(&setup.admin, &pool_ab, &200, &true);
+    assert_eq!(swap(), Err(Ok(SwapError::OracleUnavailable)));
+    oracle.set_price(&setup.token_a.symbol, &USD, &USD);
+    setup.contract.set_price_guard(&setup.admin, &pool_ab, &100, &true);
+    assert_eq!(swap(), Err(Ok(SwapError::PriceDeviationExceeded)));
+    assert_eq!(setup.balance(&setup.token_a, &user), 2 * amount_in);
+
+    setup.contract.set_price_guard(&setup.admin, &pool_ab, &200, &true);
+    assert_eq!(swap(), Ok(hop_out(amount_in, 30, RESERVE, RESERVE)));
+
+    // A zero bound removes the guard, so a broken oracle no longer matters
+    setup.contract.set_price_guard(&setup.admin, &pool_ab, &0, &false);
+    assert_eq!(setup.contract.get_price_guard(&pool_ab), None);
+    oracle.set_price(&setup.token_a.symbol, &0, &0);
+    assert!(swap().is_ok());
+    setup.assert_settled();
+}
+
+#[test]
+fn test_fee_tier_discount_and_volume() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let amount_in = 100_000_000;
+    let user = setup.trader(&setup.token_a, 4 * amount_in);
+    let steps = vec![&setup.env, setup.step(&pool_ab, &setup.token_a, &setup.token_b, amount_in)];
+    let swap = || setup.contract.execute_swap(&setup.request(&user, setup.route(steps.clone(), amount_in, 0), 0));
+
+    // A broken fee manager leaves trades at the base rate instead of failing them
+    setup.contract.set_fee_manager(&setup.admin, &Address::generate(&setup.env));
+    assert_eq!(swap().fees_paid, amount_in * 30 / 10000);
+
+    let fee_manager = MockFeeManagerClient::new(&setup.env, &setup.env.register(MockFeeManager, ()));
+    let other = Address::generate(&setup.env);
+    assert_eq!(
+        setup.contract.try_set_fee_manager(&other, &fee_manager.address),
+        Err(Ok(SwapError::Unauthorized))
+    );
+    setup.contract.set_fee_manager(&setup.admin, &fee_manager.address);
+
+    // Without an oracle the trade goes through at the user's 15 bps tier but earns no volume
+    let fee = amount_in * 15 / 10000;
+    assert_eq!(swap().fees_paid, fee);
+    assert_eq!(fee_manager.get_volume(&user), (0, 0));
+
+    // Fees and volume are reported in XLM stroops at oracle prices ($0.10 per XLM)
+    setup.set_oracle(USD, USD);
+    assert_eq!(swap().fees_paid, fee);
+    assert_eq!(fee_manager.get_volume(&user), (amount_in * 10, fee * 10));
+
+    // The fee manager records the fees for revenue distribution
+    let fee_manager = UltimateFeeManagerContractClient::new(&setup.env, &setup.env.register(UltimateFeeManagerContract, ()));
+    fee_manager.initialize(&setup.admin, &Address::generate(&setup.env), &Address::generate(&setup.env));
+    setup.contract.set_fee_manager(&setup.admin, &fee_manager.address);
+    setup.set_time(1_000);
+    let fees_paid = swap().fees_paid;
+    let revenue: RevenueDistribution = setup.env.as_contract(&fee_manager.address, || {
+        setup.env.storage().persistent().get(&FeeManagerKey::Revenue(1_000)).unwrap()
+    });
+    assert_eq!(revenue.total_revenue, fees_paid * 10);
+    assert_eq!(revenue.protocol_share + revenue.lp_share + revenue.staker_share, revenue.total_revenue);
+    setup.assert_settled();
+}
+
+#[test]
+fn test_limit_orders_fill_and_refund() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let keeper = Address::generate(&setup.env);
+    let amount_in = 100_000_000;
+    let owner = setup.trader(&setup.token_a, 3 * amount_in);
+
+    // One order the pool meets, one it does not, and one that expires
+    let market_out = hop_out(amount_in, 30, RESERVE, RESERVE);
+    let filled = setup.contract.place_limit_order(&owner, &pool_ab, &setup.token_a, &amount_in, &(market_out / 2), &1_000);
+    let resting = setup.contract.place_limit_order(&owner, &pool_ab, &setup.token_a, &amount_in, &(2 * market_out), &1_000);
+    let expiring = setup.contract.place_limit_order(&owner, &pool_ab, &setup.token_a, &amount_in, &(2 * market_out), &10);
+    assert_eq!(setup.balance(&setup.token_a, &owner), 0);
+    assert_eq!(setup.balance(&setup.token_a, &setup.contract.address), 3 * amount_in);
+
+    setup.set_time(100);
+    assert_eq!(setup.contract.fill_orders(&keeper, &pool_ab, &10), vec![&setup.env, filled]);
+    let reward = market_out * 10 / 10000;
+    assert_eq!(setup.balance(&setup.token_b, &owner), market_out - reward);
+    assert_eq!(setup.balance(&setup.token_b, &keeper), reward);
+    assert_eq!(setup.contract.get_order(&filled).status, OrderStatus::Filled);
+
+    // The expired order was refunded during the scan; the resting one stays open
+    assert_eq!(setup.contract.get_order(&expiring).status, OrderStatus::Expired);
+    assert_eq!(setup.balance(&setup.token_a, &owner), amount_in);
+    assert_eq!(setup.contract.get_pool_orders(&pool_ab).len(), 1);
+
+    let other = Address::generate(&setup.env);
+    assert_eq!(setup.contract.try_cancel_order(&other, &resting), Err(Ok(SwapError::Unauthorized)));
+    setup.contract.cancel_order(&owner, &resting);
+    assert_eq!(setup.contract.try_cancel_order(&owner, &resting), Err(Ok(SwapError::InvalidOrder)));
+    assert_eq!(setup.balance(&setup.token_a, &owner), 2 * amount_in);
+    assert_eq!(setup.contract.get_user_orders(&owner).len(), 0);
+
+    // A tripped price guard leaves the order open instead of failing the batch
+    let guarded = setup.contract.place_limit_order(&owner, &pool_ab, &setup.token_a, &amount_in, &1, &1_000);
+    setup.set_oracle(USD, USD);
+    setup.contract.set_price_guard(&setup.admin, &pool_ab, &100, &false);
+    assert_eq!(setup.contract.fill_orders(&keeper, &pool_ab, &10).len(), 0);
+    assert_eq!(setup.contract.get_order(&guarded).status, OrderStatus::Open);
+
+    setup.contract.set_price_guard(&setup.admin, &pool_ab, &0, &false);
+    assert_eq!(setup.contract.fill_orders(&keeper, &pool_ab, &10), vec![&setup.env, guarded]);
+    assert_eq!(setup.balance(&setup.token_a, &owner), amount_in);
+    setup.assert_settled();
+}
+
+#[test]
+fn test_dca_executes_installments_and_refunds() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let amount = 10_000_000;
+    let owner = setup.trader(&setup.token_a, 1_000_000_000);
+    let (token_a, token_b) = (&setup.token_a, &setup.token_b);
+
+    setup.set_time(1_000);
+    let schedule = setup.contract.create_dca(&owner, token_a, token_b, &amount, &100, &4, &100);
+    // A 5% price impact is well beyond the 1% bound
+    let oversized = setup.contract.create_dca(&owner, token_a, token_b, &500_000_000, &100, &1, &100);
+    assert_eq!(setup.balance(token_a, &owner), 1_000_000_000 - 4 * amount - 500_000_000);
+    assert_eq!(
+        setup.contract.try_create_dca(&owner, token_a, token_a, &amount, &100, &4, &100),
+        Err(Ok(SwapError::InvalidSchedule))
+    );
+
+    // Installments need the oracle reference
+    assert_eq!(
+        setup.contract.try_execute_due_dca(&vec![&setup.env, schedule]),
+        Err(Ok(SwapError::OracleUnavailable))
+    );
+    setup.set_oracle(USD, USD);
+
+    // Unknown and oversized schedules are skipped, not fatal
+    let executed = setup.contract.execute_due_dca(&vec![&setup.env, 99, oversized, schedule]);
+    assert_eq!(executed, vec![&setup.env, schedule]);
+    assert_eq!(setup.contract.execute_due_dca(&vec![&setup.env, schedule]).len(), 0);
+    let state = setup.contract.get_dca(&schedule);
+    assert_eq!((state.periods_executed, state.next_execution), (1, 1_100));
+    assert_eq!(setup.balance(token_b, &owner), state.total_received);
+    assert_eq!(state.total_received, hop_out(amount, 30, RESERVE, RESERVE));
+
+    // A late keeper runs one installment, not every missed one
+    setup.set_time(1_250);
+    assert_eq!(setup.contract.execute_due_dca(&vec![&setup.env, schedule]).len(), 1);
+    assert_eq!(setup.contract.execute_due_dca(&vec![&setup.env, schedule]).len(), 0);
+    assert_eq!(setup.contract.get_dca(&schedule).next_execution, 1_300);
+
+    // Moving the pool away from the oracle price just before execution holds the installment back
+    setup.set_time(1_300);
+    let whale = setup.trader(token_a, 1_000_000_000);
+    let steps = vec![&setup.env, setup.step(&pool_ab, token_a, token_b, 1_000_000_000)];
+    setup.contract.execute_swap(&setup.request(&whale, setup.route(steps, 1_000_000_000, 0), 0));
+    assert_eq!(setup.contract.execute_due_dca(&vec![&setup.env, schedule]).len(), 0);
+
+    // Cancelling refunds the unspent installments
+    let other = Address::generate(&setup.env);
+    assert_eq!(setup.contract.try_cancel_dca(&other, &schedule), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(setup.contract.cancel_dca(&owner, &schedule), 2 * amount);
+    assert_eq!(setup.contract.cancel_dca(&owner, &oversized), 500_000_000);
+    assert_eq!(setup.contract.try_cancel_dca(&owner, &schedule), Err(Ok(SwapError::InvalidSchedule)));
+    assert_eq!(setup.balance(token_a, &owner), 1_000_000_000 - 2 * amount);
+    assert_eq!(setup.contract.get_user_dcas(&owner).len(), 0);
+    setup.assert_settled();
+}
+
+#[test]
+fn test_quotes_match_execution() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let pool_bc = setup.seed("B_C", &setup.token_b, &setup.token_c);
+    let user = setup.trader(&setup.token_a, 1_000_000_000);
+    let steps = vec![
+        &setup.env,
+        setup.step(&pool_ab, &setup.token_a, &setup.token_b, 0),
+        setup.step(&pool_bc, &setup.token_b, &setup.token_c, 0),
+    ];
+
+    let amount_in = 200_000_000;
+    let route = setup.route(steps.clone(), amount_in, 0);
+    let quote = setup.contract.quote_exact_in(&user, &route, &50);
+    assert_eq!(quote.hops.len(), 2);
+    assert_eq!(quote.total_fees, quote.hops.get_unchecked(0).fee + quote.hops.get_unchecked(1).fee);
+    assert_eq!(quote.hops.get_unchecked(0).amount_out, quote.hops.get_unchecked(1).amount_in);
+    assert_eq!(quote.hops.get_unchecked(0).spot_price_before, PRICE_PRECISION);
+    assert!(quote.price_impact_bps > 0);
+    assert_eq!(quote.min_amount_out, quote.amount_out * 9_950 / 10_000);
+    assert_eq!(
+        setup.contract.try_quote_exact_in(&user, &route, &(MAX_SLIPPAGE + 1)),
+        Err(Ok(SwapError::InvalidSlippage))
+    );
+
+    let route = setup.route(steps.clone(), amount_in, quote.amount_out);
+    let result = setup.contract.execute_swap(&setup.request(&user, route, quote.min_amount_out));
+    assert_eq!((result.amount_out, result.fees_paid), (quote.amount_out, quote.total_fees));
+
+    // The exact-out quote is priced on the reserves the first swap left behind
+    let amount_out = 30_000_000;
+    let route = setup.route(steps, 0, amount_out);
+    let quote = setup.contract.quote_exact_out(&user, &route, &50);
+    assert_eq!(quote.max_amount_in, quote.amount_in * 10_050 / 10_000);
+    let result = setup.contract.swap_tokens_for_exact_tokens(&user, &route, &amount_out, &quote.max_amount_in, &u64::MAX);
+    assert_eq!((result.amount_in, result.fees_paid), (quote.amount_in, quote.total_fees));
+    setup.assert_settled();
+
+    // Quotes run the price guard the swap would
+    setup.contract.set_price_guard(&setup.admin, &pool_ab, &1, &false);
+    assert_eq!(setup.contract.try_quote_exact_out(&user, &route, &50), Err(Ok(SwapError::OracleUnavailable)));
+    setup.set_oracle(USD, 0);
+    assert_eq!(setup.contract.try_quote_exact_out(&user, &route, &50), Err(Ok(SwapError::PriceDeviationExceeded)));
+    setup.contract.set_price_guard(&setup.admin, &pool_ab, &MAX_PRICE_DEVIATION, &false);
+    assert_eq!(setup.contract.quote_exact_out(&user, &route, &50).amount_out, amount_out);
+}
+
+#[test]
+fn test_create_pool_reads_token_metadata() {
+    let setup = Setup::new();
+    let mut spoofed = setup.token_a.clone();
+    spoofed.symbol = String::from_str(&setup.env, "USDC");
+    spoofed.decimals = 18;
+
+    let pool_id = setup.contract.create_pool(&spoofed, &setup.token_b, &30);
+    let pool = setup.contract.get_pool(&pool_id);
+    assert!(pool.token_a == setup.token_a || pool.token_b == setup.token_a);
+    assert_eq!(
+        setup.contract.try_create_pool(&setup.token_b, &setup.token_a, &30),
+        Err(Ok(SwapError::PoolAlreadyExists))
+    );
+}
+
+#[test]
+fn test_exact_out_pays_least_input() {
+    let setup = Setup::new();
+    let pool_ab = setup.seed("A_B", &setup.token_a, &setup.token_b);
+    let pool_bc = setup.seed("B_C", &setup.token_b, &setup.token_c);
+    let user = setup.trader(&setup.token_a, 1_000_000_000);
+    let steps = vec![
+        &setup.env,
+        setup.step(&pool_ab, &setup.token_a, &setup.token_b, 0),
+        setup.step(&pool_bc, &setup.token_b, &setup.token_c, 0),
+    ];
+
+    for amount_out in [50_000_000, 99_999_999, 123_456_789] {
+        let quote = setup.contract.quote_exact_out(&user, &setup.route(steps.clone(), 0, amount_out), &0);
+
+        // The quoted input buys at least the output, and one unit less falls short
+        let exact_in = setup.contract.quote_exact_in(&user, &setup.route(steps.clone(), quote.amount_in, 0), &0);
+        let short_in = setup.contract.quote_exact_in(&user, &setup.route(steps.clone(), quote.amount_in - 1, 0), &0);
+        assert!(exact_in.amount_out >= amount_out);
+        assert!(short_in.amount_out < amount_out);
+    }
+
+    let amount_out = 50_000_000;
+    let route = setup.route(steps, 0, amount_out);
+    let quote = setup.contract.quote_exact_out(&user, &route, &0);
+    assert_eq!(
+        setup.contract.try_swap_tokens_for_exact_tokens(&user, &route, &amount_out, &(quote.amount_in - 1), &u64::MAX),
+        Err(Ok(SwapError::SlippageExceeded))
+    );
+
+    let result = setup.contract.swap_tokens_for_exact_tokens(&user, &route, &amount_out, &quote.amount_in, &u64::MAX);
+    assert_eq!((result.amount_in, result.amount_out), (quote.amount_in, amount_out));
+    assert_eq!(setup.balance(&setup.token_c, &user), amount_out);
+    assert_eq!(setup.balance(&setup.token_a, &user), 1_000_000_000 - quote.amount_in);
+    setup.assert_settled();
+}
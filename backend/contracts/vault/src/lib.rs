@@ -0,0 +1,867 @@
+#![no_std]
+
+//! # SWAVE Auto-Compounding Vault Contract
+//!
+//! Holds an LP position in one liquidity pool on behalf of its depositors.
+//! Depositors receive vault shares; the vault's LP shares grow as rewards are
+//! compounded, so each vault share redeems for more liquidity over time.
+//!
+//! ## Features
+//! - Deposits and withdrawals routed through the liquidity contract, with the
+//!   vault's LP shares staked there so the whole position earns pool rewards
+//! - `harvest`, run by admin-approved keepers, that claims every pool reward, swaps
+//!   it into the pool's two tokens through the swap contract and re-adds it as liquidity
+//! - Harvest bounty paid to the keeper out of the claimed rewards
+//! - Shares priced on the LP position plus idle tokens, with shares locked on the
+//!   first deposit so donations cannot inflate the share price
+//!
+//! Tokens the vault cannot put to work immediately (ratio excess, failed swaps)
+//! stay in the vault and are retried on the next harvest.
+
+use soroban_sdk::{
+    auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation},
+    contract, contractclient, contractimpl, contracttype, contracterror, contractmeta,
+    token::TokenClient, vec, Address, Env, IntoVal, String, Symbol, Val, Vec,
+};
+
+// Contract metadata
+contractmeta!(
+    key = "Description",
+    val = "SWAVE Auto-Compounding Vault - Harvest, Swap & Re-add Liquidity"
+);
+
+contractmeta!(key = "Version", val = "1.0.0");
+
+/// Vault constants
+const BASIS_POINTS: i128 = 10_000;
+const MAX_BOUNTY_BPS: u32 = 500;            // Keepers get at most 5% of a harvest
+const HARVEST_SLIPPAGE: u32 = 100;          // 1% tolerance on harvest swaps
+const MAX_ROUTE_HOPS: u32 = 3;              // Longest route used to sell rewards
+const MINIMUM_SHARES: i128 = 1_000;         // Vault shares locked forever on the first deposit
+
+/// Contract errors
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum VaultError {
+    AlreadyInitialized = 1,
+    NotInitialized = 2,
+    Unauthorized = 3,
+    InvalidAmount = 4,
+    InsufficientShares = 5,
+    TokenNotSupported = 6,
+    InvalidConfig = 7,
+    PoolCallFailed = 8,
+}
+
+/// Token info as registered with the swap contract
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Token {
+    /// Token contract address (None for native XLM)
+    pub address: Option<Address>,
+    /// Token symbol
+    pub symbol: String,
+    /// Token decimals
+    pub decimals: u32,
+}
+
+/// Swap route step
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwapStep {
+    /// Input token
+    pub token_in: Token,
+    /// Output token
+    pub token_out: Token,
+    /// Pool/exchange identifier
+    pub pool_id: String,
+    /// Expected amount in
+    pub amount_in: i128,
+    /// Expected amount out
+    pub amount_out: i128,
+    /// Fee for this step
+    pub fee: i128,
+}
+
+/// Swap route returned by the swap contract
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwapRoute {
+    /// Swap steps in order
+    pub steps: Vec<SwapStep>,
+    /// Total input amount
+    pub total_amount_in: i128,
+    /// Total expected output
+    pub total_amount_out: i128,
+    /// Total fees
+    pub total_fees: i128,
+    /// Expected slippage (basis points)
+    pub expected_slippage: u32,
+    /// Route expiry timestamp
+    pub expires_at: u64,
+}
+
+/// Swap execution request
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwapRequest {
+    /// User executing the swap
+    pub user: Address,
+    /// Route to execute
+    pub route: SwapRoute,
+    /// Maximum slippage tolerance (basis points)
+    pub max_slippage: u32,
+    /// Minimum amount out (slippage protection)
+    pub min_amount_out: i128,
+    /// Deadline timestamp
+    pub deadline: u64,
+}
+
+/// Swap execution result
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwapResult {
+    /// Actual amount spent
+    pub amount_in: i128,
+    /// Actual amount received
+    pub amount_out: i128,
+    /// Actual fees paid
+    pub fees_paid: i128,
+    /// Actual slippage experienced
+    pub actual_slippage: u32,
+    /// Execution timestamp
+    pub executed_at: u64,
+}
+
+/// Pool info as reported by the swap contract
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pool {
+    /// Pool identifier
+    pub id: String,
+    /// Token A
+    pub token_a: Token,
+    /// Token B
+    pub token_b: Token,
+    /// Reserve A
+    pub reserve_a: i128,
+    /// Reserve B
+    pub reserve_b: i128,
+    /// Pool fee (basis points)
+    pub fee: u32,
+    /// Pool enabled
+    pub enabled: bool,
+}
+
+/// Pool reserves as reported by the liquidity contract
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolReserves {
+    /// Token A contract
+    pub token_a: Address,
+    /// Token B contract
+    pub token_b: Address,
+    /// Reserve A
+    pub reserve_a: i128,
+    /// Reserve B
+    pub reserve_b: i128,
+}
+
+/// Amounts taken by a liquidity deposit and the LP shares minted for them
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LiquidityDeposit {
+    /// Token A deposited
+    pub amount_a: i128,
+    /// Token B deposited
+    pub amount_b: i128,
+    /// LP shares minted
+    pub lp_tokens: i128,
+}
+
+/// Reward tokens paid by a claim
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RewardPayout {
+    /// Reward token
+    pub reward_token: Address,
+    /// Amount transferred
+    pub amount: i128,
+}
+
+/// Vault configuration
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VaultConfig {
+    /// Vault admin
+    pub admin: Address,
+    /// Liquidity contract holding the pool
+    pub liquidity_contract: Address,
+    /// Swap contract used to sell rewards
+    pub swap_contract: Address,
+    /// Pool this vault compounds
+    pub pool_id: String,
+    /// Pool token A
+    pub token_a: Token,
+    /// Pool token B
+    pub token_b: Token,
+    /// Pool LP share token
+    pub lp_token: Address,
+    /// Share of each harvest paid to the keeper (basis points)
+    pub bounty_bps: u32,
+}
+
+/// Vault accounting
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VaultState {
+    /// Vault shares outstanding
+    pub total_shares: i128,
+    /// LP shares added by harvests
+    pub lp_compounded: i128,
+    /// Number of harvests
+    pub harvest_count: u32,
+    /// Last harvest timestamp
+    pub last_harvest: u64,
+}
+
+/// Result of a vault deposit
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VaultDeposit {
+    /// Token A deposited into the pool
+    pub amount_a: i128,
+    /// Token B deposited into the pool
+    pub amount_b: i128,
+    /// Vault shares minted
+    pub shares: i128,
+}
+
+/// Result of a harvest
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HarvestReport {
+    /// Rewards claimed from the pool
+    pub rewards: Vec<RewardPayout>,
+    /// Bounty paid to the keeper
+    pub bounty: Vec<RewardPayout>,
+    /// LP shares added to the vault
+    pub lp_tokens_added: i128,
+}
+
+/// Storage keys
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    /// Vault configuration
+    Config,
+    /// Vault accounting
+    State,
+    /// Vault shares of a depositor
+    Shares(Address),
+    /// Reward tokens the vault sells on harvest
+    RewardTokens,
+    /// Keeper approved to harvest
+    Keeper(Address),
+}
+
+/// Liquidity contract interface used to manage the vault's position
+#[contractclient(name = "LiquidityClient")]
+pub trait LiquidityContractInterface {
+    fn get_reserves(env: Env, pool_id: String) -> PoolReserves;
+    fn get_lp_token(env: Env, pool_id: String) -> Address;
+    #[allow(clippy::too_many_arguments)]
+    fn add_liquidity(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+        amount_a_desired: i128,
+        amount_b_desired: i128,
+        amount_a_min: i128,
+        amount_b_min: i128,
+        deadline: u64,
+    ) -> LiquidityDeposit;
+    fn remove_liquidity(
+        env: Env,
+        provider: Address,
+        pool_id: String,
+        lp_tokens: i128,
+        min_a: i128,
+        min_b: i128,
+        deadline: u64,
+    ) -> (i128, i128);
+    fn claim_all(env: Env, provider: Address, pool_id: String) -> Vec<RewardPayout>;
+    fn stake(env: Env, provider: Address, pool_id: String, lp_tokens: i128) -> i128;
+    fn unstake(env: Env, provider: Address, pool_id: String, lp_tokens: i128) -> i128;
+    fn get_staked_shares(env: Env, provider: Address, pool_id: String) -> i128;
+}
+
+/// LP share token interface used to price idle tokens in LP shares
+#[contractclient(name = "LpTokenClient")]
+pub trait LpTokenInterface {
+    fn total_supply(env: Env) -> i128;
+}
+
+/// Swap contract interface used to sell harvested rewards
+#[contractclient(name = "SwapClient")]
+pub trait SwapContractInterface {
+    fn get_pool(env: Env, pool_id: String) -> Pool;
+    fn get_best_route(env: Env, token_in: Token, token_out: Token, amount_in: i128, max_hops: u32) -> SwapRoute;
+    fn execute_swap(env: Env, request: SwapRequest) -> SwapResult;
+}
+
+/// SWAVE Auto-Compounding Vault Contract
+#[contract]
+pub struct VaultContract;
+
+#[contractimpl]
+impl VaultContract {
+    
+    /// Initialize the vault for one pool
+    pub fn initialize(
+        env: Env,
+        admin: Address,
+        liquidity_contract: Address,
+        swap_contract: Address,
+        pool_id: String,
+        bounty_bps: u32,
+    ) -> Result<(), VaultError> {
+        if env.storage().instance().has(&DataKey::Config) {
+            return Err(VaultError::AlreadyInitialized);
+        }
+        admin.require_auth();
+        
+        if bounty_bps > MAX_BOUNTY_BPS {
+            return Err(VaultError::InvalidConfig);
+        }
+        
+        // Pool tokens as the swap contract knows them, so routes can be searched
+        let pool = SwapClient::new(&env, &swap_contract).get_pool(&pool_id);
+        if pool.token_a.address.is_none() || pool.token_b.address.is_none() {
+            return Err(VaultError::TokenNotSupported);
+        }
+        let lp_token = LiquidityClient::new(&env, &liquidity_contract).get_lp_token(&pool_id);
+        
+        let config = VaultConfig {
+            admin,
+            liquidity_contract,
+            swap_contract,
+            pool_id,
+            token_a: pool.token_a,
+            token_b: pool.token_b,
+            lp_token,
+            bounty_bps,
+        };
+        
+        let state = VaultState {
+            total_shares: 0,
+            lp_compounded: 0,
+            harvest_count: 0,
+            last_harvest: 0,
+        };
+        
+        env.storage().instance().set(&DataKey::Config, &config);
+        env.storage().instance().set(&DataKey::State, &state);
+        env.storage().instance().set(&DataKey::RewardTokens, &Vec::<Token>::new(&env));
+        
+        Ok(())
+    }
+
+    /// Deposit pool tokens and receive vault shares.
+    /// Only the amounts matching the pool ratio are used; the excess is returned.
+    #[allow(clippy::too_many_arguments)]
+    pub fn deposit(
+        env: Env,
+        user: Address,
+        amount_a: i128,
+        amount_b: i128,
+        amount_a_min: i128,
+        amount_b_min: i128,
+        deadline: u64,
+    ) -> Result<VaultDeposit, VaultError> {
+        user.require_auth();
+        
+        if amount_a <= 0 || amount_b <= 0 {
+            return Err(VaultError::InvalidAmount);
+        }
+        
+        let config = Self::get_config(env.clone())?;
+        let mut state = Self::get_state(env.clone())?;
+        let vault = env.current_contract_address();
+        let token_a = TokenClient::new(&env, &Self::token_address(&config.token_a)?);
+        let token_b = TokenClient::new(&env, &Self::token_address(&config.token_b)?);
+        let value_before = Self::lp_value(&env, &config)?;
+        
+        token_a.transfer(&user, &vault, &amount_a);
+        token_b.transfer(&user, &vault, &amount_b);
+        
+        let deposit = Self::add_to_pool(&env, &config, amount_a, amount_b, amount_a_min, amount_b_min, deadline)?;
+        Self::stake_lp(&env, &config)?;
+        
+        // Return what the pool ratio did not need
+        if amount_a > deposit.amount_a {
+            token_a.transfer(&vault, &user, &(amount_a - deposit.amount_a));
+        }
+        if amount_b > deposit.amount_b {
+            token_b.transfer(&vault, &user, &(amount_b - deposit.amount_b));
+        }
+        
+        // Price shares against everything the vault held before this deposit; the first
+        // deposit locks MINIMUM_SHARES so the share price cannot be inflated from one share
+        let shares = if state.total_shares == 0 || value_before == 0 {
+            deposit.lp_tokens
+        } else {
+            Self::mul_div(deposit.lp_tokens, state.total_shares, value_before)?
+        };
+        let locked = if state.total_shares == 0 { MINIMUM_SHARES } else { 0 };
+        let shares = shares - locked;
+        if shares <= 0 {
+            return Err(VaultError::InvalidAmount);
+        }
+        
+        state.total_shares += shares + locked;
+        env.storage().instance().set(&DataKey::State, &state);
+        let key = DataKey::Shares(user.clone());
+        let balance = Self::get_shares(env.clone(), user.clone());
+        env.storage().persistent().set(&key, &(balance + shares));
+        
+        env.events().publish(
+            (Symbol::new(&env, "vault_deposit"),),
+            (user, deposit.amount_a, deposit.amount_b, shares)
+        );
+        
+        Ok(VaultDeposit {
+            amount_a: deposit.amount_a,
+            amount_b: deposit.amount_b,
+            shares,
+        })
+    }
+
+    /// Redeem vault shares for the pool tokens behind them
+    pub fn withdraw(
+        env: Env,
+        user: Address,
+        shares: i128,
+        min_a: i128,
+        min_b: i128,
+        deadline: u64,
+    ) -> Result<(i128, i128), VaultError> {
+        user.require_auth();
+        
+        let balance = Self::get_shares(env.clone(), user.clone());
+        if shares <= 0 || shares > balance {
+            return Err(VaultError::InsufficientShares);
+        }
+        
+        let config = Self::get_config(env.clone())?;
+        let mut state = Self::get_state(env.clone())?;
+        let vault = env.current_contract_address();
+        let token_a = TokenClient::new(&env, &Self::token_address(&config.token_a)?);
+        let token_b = TokenClient::new(&env, &Self::token_address(&config.token_b)?);
+        
+        // The user's part of the LP position and of any tokens waiting to be compounded
+        let lp_tokens = Self::mul_div(Self::staked_lp(&env, &config), shares, state.total_shares)?;
+        let idle_a = Self::mul_div(token_a.balance(&vault), shares, state.total_shares)?;
+        let idle_b = Self::mul_div(token_b.balance(&vault), shares, state.total_shares)?;
+        
+        let (mut amount_a, mut amount_b) = (0i128, 0i128);
+        if lp_tokens > 0 {
+            let liquidity = LiquidityClient::new(&env, &config.liquidity_contract);
+            liquidity
+                .try_unstake(&vault, &config.pool_id, &lp_tokens)
+                .map_err(|_| VaultError::PoolCallFailed)?
+                .map_err(|_| VaultError::PoolCallFailed)?;
+            
+            // The liquidity contract burns the vault's LP shares
+            Self::authorize(
+                &env,
+                &config.lp_token,
+                "burn",
+                (vault.clone(), lp_tokens).into_val(&env),
+            );
+            (amount_a, amount_b) = liquidity
+                .try_remove_liquidity(&vault, &config.pool_id, &lp_tokens, &min_a, &min_b, &deadline)
+                .map_err(|_| VaultError::PoolCallFailed)?
+                .map_err(|_| VaultError::PoolCallFailed)?;
+        }
+        amount_a += idle_a;
+        amount_b += idle_b;
+        
+        state.total_shares -= shares;
+        env.storage().instance().set(&DataKey::State, &state);
+        env.storage().persistent().set(&DataKey::Shares(user.clone()), &(balance - shares));
+        
+        if amount_a > 0 {
+            token_a.transfer(&vault, &user, &amount_a);
+        }
+        if amount_b > 0 {
+            token_b.transfer(&vault, &user, &amount_b);
+        }
+        
+        env.events().publish(
+            (Symbol::new(&env, "vault_withdraw"),),
+            (user, amount_a, amount_b, shares)
+        );
+        
+        Ok((amount_a, amount_b))
+    }
+
+    /// Claim pool rewards, sell them for the pool tokens and re-add them as liquidity.
+    /// Only approved keepers may call this, since the swaps are priced on routes found in
+    /// the same transaction; the keeper receives `bounty_bps` of every claimed reward.
+    pub fn harvest(env: Env, keeper: Address) -> Result<HarvestReport, VaultError> {
+        keeper.require_auth();
+        
+        if !Self::is_keeper(env.clone(), keeper.clone()) {
+            return Err(VaultError::Unauthorized);
+        }
+        
+        let config = Self::get_config(env.clone())?;
+        let mut state = Self::get_state(env.clone())?;
+        let vault = env.current_contract_address();
+        let token_a = Self::token_address(&config.token_a)?;
+        let token_b = Self::token_address(&config.token_b)?;
+        
+        let rewards = LiquidityClient::new(&env, &config.liquidity_contract)
+            .try_claim_all(&vault, &config.pool_id)
+            .map_err(|_| VaultError::PoolCallFailed)?
+            .map_err(|_| VaultError::PoolCallFailed)?;
+        
+        // Pay the keeper first, then swap half of any pool-token reward into the other side
+        let mut bounty = Vec::new(&env);
+        for payout in rewards.iter() {
+            let fee = payout.amount * config.bounty_bps as i128 / BASIS_POINTS;
+            if fee > 0 {
+                TokenClient::new(&env, &payout.reward_token).transfer(&vault, &keeper, &fee);
+                bounty.push_back(RewardPayout { reward_token: payout.reward_token.clone(), amount: fee });
+            }
+            
+            let half = (payout.amount - fee) / 2;
+            if payout.reward_token == token_a {
+                Self::swap_reward(&env, &config, &config.token_a, &config.token_b, half);
+            } else if payout.reward_token == token_b {
+                Self::swap_reward(&env, &config, &config.token_b, &config.token_a, half);
+            }
+        }
+        
+        // Sell the whole balance of other reward tokens, including leftovers of earlier harvests
+        for reward in Self::get_reward_tokens(env.clone()).iter() {
+            let address = Self::token_address(&reward)?;
+            let balance = TokenClient::new(&env, &address).balance(&vault);
+            let half = balance / 2;
+            Self::swap_reward(&env, &config, &reward, &config.token_a, half);
+            Self::swap_reward(&env, &config, &reward, &config.token_b, balance - half);
+        }
+        
+        // Re-add everything waiting in the vault; amounts below the pool minimum wait for the next harvest
+        let idle_a = TokenClient::new(&env, &token_a).balance(&vault);
+        let idle_b = TokenClient::new(&env, &token_b).balance(&vault);
+        let lp_tokens_added = if idle_a > 0 && idle_b > 0 {
+            Self::add_to_pool(&env, &config, idle_a, idle_b, 0, 0, env.ledger().timestamp())
+                .map(|deposit| deposit.lp_tokens)
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        Self::stake_lp(&env, &config)?;
+        
+        state.lp_compounded += lp_tokens_added;
+        state.harvest_count += 1;
+        state.last_harvest = env.ledger().timestamp();
+        env.storage().instance().set(&DataKey::State, &state);
+        
+        env.events().publish(
+            (Symbol::new(&env, "vault_harvest"),),
+            (keeper, lp_tokens_added)
+        );
+        
+        Ok(HarvestReport {
+            rewards,
+            bounty,
+            lp_tokens_added,
+        })
+    }
+
+    /// Register a reward token to sell on harvest (admin only)
+    pub fn add_reward_token(env: Env, caller: Address, token: Token) -> Result<(), VaultError> {
+        let config = Self::require_admin(&env, &caller)?;
+        
+        Self::token_address(&token)?;
+        if token == config.token_a || token == config.token_b {
+            return Err(VaultError::InvalidConfig);
+        }
+        
+        let mut tokens = Self::get_reward_tokens(env.clone());
+        if !tokens.contains(&token) {
+            tokens.push_back(token);
+            env.storage().instance().set(&DataKey::RewardTokens, &tokens);
+        }
+        
+        Ok(())
+    }
+
+    /// Approve or revoke a harvest keeper (admin only)
+    pub fn set_keeper(env: Env, caller: Address, keeper: Address, approved: bool) -> Result<(), VaultError> {
+        Self::require_admin(&env, &caller)?;
+        
+        let key = DataKey::Keeper(keeper);
+        if approved {
+            env.storage().persistent().set(&key, &true);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+        
+        Ok(())
+    }
+
+    /// Whether a keeper may harvest
+    pub fn is_keeper(env: Env, keeper: Address) -> bool {
+        env.storage().persistent().has(&DataKey::Keeper(keeper))
+    }
+
+    /// Update the keeper bounty (admin only)
+    pub fn set_bounty(env: Env, caller: Address, bounty_bps: u32) -> Result<(), VaultError> {
+        let mut config = Self::require_admin(&env, &caller)?;
+        
+        if bounty_bps > MAX_BOUNTY_BPS {
+            return Err(VaultError::InvalidConfig);
+        }
+        
+        config.bounty_bps = bounty_bps;
+        env.storage().instance().set(&DataKey::Config, &config);
+        
+        Ok(())
+    }
+
+    /// Get vault configuration
+    pub fn get_config(env: Env) -> Result<VaultConfig, VaultError> {
+        env.storage().instance()
+            .get(&DataKey::Config)
+            .ok_or(VaultError::NotInitialized)
+    }
+
+    /// Get vault accounting
+    pub fn get_state(env: Env) -> Result<VaultState, VaultError> {
+        env.storage().instance()
+            .get(&DataKey::State)
+            .ok_or(VaultError::NotInitialized)
+    }
+
+    /// Reward tokens sold on harvest
+    pub fn get_reward_tokens(env: Env) -> Vec<Token> {
+        env.storage().instance()
+            .get(&DataKey::RewardTokens)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Vault shares of a depositor
+    pub fn get_shares(env: Env, user: Address) -> i128 {
+        env.storage().persistent()
+            .get(&DataKey::Shares(user))
+            .unwrap_or(0)
+    }
+
+    /// LP shares currently backing a depositor's vault shares
+    pub fn get_user_lp_tokens(env: Env, user: Address) -> Result<i128, VaultError> {
+        let config = Self::get_config(env.clone())?;
+        let state = Self::get_state(env.clone())?;
+        if state.total_shares == 0 {
+            return Ok(0);
+        }
+        
+        let lp_balance = Self::staked_lp(&env, &config);
+        Self::mul_div(Self::get_shares(env, user), lp_balance, state.total_shares)
+    }
+
+    // Helper functions
+
+    fn require_admin(env: &Env, caller: &Address) -> Result<VaultConfig, VaultError> {
+        caller.require_auth();
+        
+        let config = Self::get_config(env.clone())?;
+        if *caller != config.admin {
+            return Err(VaultError::Unauthorized);
+        }
+        Ok(config)
+    }
+
+    fn token_address(token: &Token) -> Result<Address, VaultError> {
+        token.address.clone().ok_or(VaultError::TokenNotSupported)
+    }
+
+    /// Add the vault's tokens to the pool; the liquidity contract pulls them from the vault
+    #[allow(clippy::too_many_arguments)]
+    fn add_to_pool(
+        env: &Env,
+        config: &VaultConfig,
+        amount_a: i128,
+        amount_b: i128,
+        amount_a_min: i128,
+        amount_b_min: i128,
+        deadline: u64,
+    ) -> Result<LiquidityDeposit, VaultError> {
+        let liquidity = LiquidityClient::new(env, &config.liquidity_contract);
+        let reserves = liquidity.get_reserves(&config.pool_id);
+        
+        // Authorize exactly the transfers the liquidity contract will make
+        let (take_a, take_b) = Self::deposit_amounts(&reserves, amount_a, amount_b)?;
+        let vault = env.current_contract_address();
+        Self::authorize(
+            env,
+            &reserves.token_a,
+            "transfer",
+            (vault.clone(), config.liquidity_contract.clone(), take_a).into_val(env),
+        );
+        Self::authorize(
+            env,
+            &reserves.token_b,
+            "transfer",
+            (vault.clone(), config.liquidity_contract.clone(), take_b).into_val(env),
+        );
+        
+        liquidity
+            .try_add_liquidity(
+                &vault,
+                &config.pool_id,
+                &amount_a,
+                &amount_b,
+                &amount_a_min,
+                &amount_b_min,
+                &deadline,
+            )
+            .map_err(|_| VaultError::PoolCallFailed)?
+            .map_err(|_| VaultError::PoolCallFailed)
+    }
+
+    /// LP shares the vault has staked with the liquidity contract
+    fn staked_lp(env: &Env, config: &VaultConfig) -> i128 {
+        LiquidityClient::new(env, &config.liquidity_contract)
+            .get_staked_shares(&env.current_contract_address(), &config.pool_id)
+    }
+
+    /// Vault holdings in LP shares: the staked position plus idle pool tokens at the pool's
+    /// current ratio, since withdrawals pay idle tokens out pro rata alongside the LP shares
+    fn lp_value(env: &Env, config: &VaultConfig) -> Result<i128, VaultError> {
+        let vault = env.current_contract_address();
+        let staked = Self::staked_lp(env, config);
+        let reserves = LiquidityClient::new(env, &config.liquidity_contract).get_reserves(&config.pool_id);
+        let idle_a = TokenClient::new(env, &reserves.token_a).balance(&vault);
+        let idle_b = TokenClient::new(env, &reserves.token_b).balance(&vault);
+        if (idle_a == 0 && idle_b == 0) || reserves.reserve_a == 0 || reserves.reserve_b == 0 {
+            return Ok(staked);
+        }
+        
+        // One LP share is worth 2 * reserve / supply of either token at the pool price
+        let supply = LpTokenClient::new(env, &config.lp_token).total_supply();
+        let idle_lp = Self::mul_div(idle_a, supply, reserves.reserve_a * 2)?
+            + Self::mul_div(idle_b, supply, reserves.reserve_b * 2)?;
+        Ok(staked + idle_lp)
+    }
+
+    /// Stake every LP share the vault holds, so the whole position earns pool rewards
+    fn stake_lp(env: &Env, config: &VaultConfig) -> Result<(), VaultError> {
+        let vault = env.current_contract_address();
+        let balance = TokenClient::new(env, &config.lp_token).balance(&vault);
+        if balance <= 0 {
+            return Ok(());
+        }
+        
+        // The liquidity contract takes the shares into custody
+        Self::authorize(
+            env,
+            &config.lp_token,
+            "transfer",
+            (vault.clone(), config.liquidity_contract.clone(), balance).into_val(env),
+        );
+        LiquidityClient::new(env, &config.liquidity_contract)
+            .try_stake(&vault, &config.pool_id, &balance)
+            .map_err(|_| VaultError::PoolCallFailed)?
+            .map_err(|_| VaultError::PoolCallFailed)?;
+        
+        Ok(())
+    }
+
+    /// Sell `amount` of a reward along the best on-chain route; returns the amount received
+    fn swap_reward(env: &Env, config: &VaultConfig, from: &Token, to: &Token, amount: i128) -> i128 {
+        let Some(from_address) = from.address.clone() else {
+            return 0;
+        };
+        if amount <= 0 {
+            return 0;
+        }
+        
+        let swap = SwapClient::new(env, &config.swap_contract);
+        let route = match swap.try_get_best_route(from, to, &amount, &MAX_ROUTE_HOPS) {
+            Ok(Ok(route)) => route,
+            _ => return 0,
+        };
+        let min_amount_out = route.total_amount_out * (BASIS_POINTS - HARVEST_SLIPPAGE as i128) / BASIS_POINTS;
+        
+        // The swap contract moves the input from the vault into the liquidity contract
+        let vault = env.current_contract_address();
+        Self::authorize(
+            env,
+            &from_address,
+            "transfer",
+            (vault.clone(), config.liquidity_contract.clone(), amount).into_val(env),
+        );
+        
+        let request = SwapRequest {
+            user: vault,
+            route,
+            max_slippage: HARVEST_SLIPPAGE,
+            min_amount_out,
+            deadline: env.ledger().timestamp(),
+        };
+        
+        match swap.try_execute_swap(&request) {
+            Ok(Ok(result)) => result.amount_out,
+            _ => 0,
+        }
+    }
+
+    /// Pre-authorize a call another contract makes on the vault's behalf
+    fn authorize(env: &Env, contract: &Address, fn_name: &str, args: Vec<Val>) {
+        env.authorize_as_current_contract(vec![
+            env,
+            InvokerContractAuthEntry::Contract(SubContractInvocation {
+                context: ContractContext {
+                    contract: contract.clone(),
+                    fn_name: Symbol::new(env, fn_name),
+                    args,
+                },
+                sub_invocations: vec![env],
+            }),
+        ]);
+    }
+
+    /// Amounts the liquidity contract takes for a deposit (mirrors its ratio matching)
+    fn deposit_amounts(
+        reserves: &PoolReserves,
+        amount_a: i128,
+        amount_b: i128,
+    ) -> Result<(i128, i128), VaultError> {
+        if reserves.reserve_a == 0 || reserves.reserve_b == 0 {
+            return Ok((amount_a, amount_b));
+        }
+        
+        let optimal_b = Self::mul_div(amount_a, reserves.reserve_b, reserves.reserve_a)?;
+        if optimal_b <= amount_b {
+            return Ok((amount_a, optimal_b));
+        }
+        
+        let optimal_a = Self::mul_div(amount_b, reserves.reserve_a, reserves.reserve_b)?;
+        Ok((optimal_a, amount_b))
+    }
+
+    /// `a * b / c` with overflow checking
+    fn mul_div(a: i128, b: i128, c: i128) -> Result<i128, VaultError> {
+        a.checked_mul(b)
+            .and_then(|product| product.checked_div(c))
+            .ok_or(VaultError::InvalidAmount)
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,215 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::{
+    testutils::{Address as _, Ledger},
+    token::StellarAssetClient,
+};
+use swave_liquidity::{UltimateLiquidityContract, UltimateLiquidityContractClient};
+use swave_swap::{SwapContract, SwapContractClient};
+
+const RESERVE: i128 = 10_000_000_000;       // 1,000 tokens at 7 decimals
+const DEPOSIT: i128 = 1_000_000_000;        // 100 tokens at 7 decimals
+
+struct Setup {
+    env: Env,
+    admin: Address,
+    liquidity: UltimateLiquidityContractClient<'static>,
+    vault: VaultContractClient<'static>,
+    pool_id: String,
+    token_a: Address,
+    token_b: Address,
+    reward: Address,
+}
+
+impl Setup {
+    /// Liquidity and swap contracts with an A/B pool for the vault and a reward/A pool to sell into,
+    /// with A as a route base so rewards reach B in two hops
+    fn new() -> Self {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.cost_estimate().budget().reset_unlimited();
+
+        let admin = Address::generate(&env);
+        let liquidity = UltimateLiquidityContractClient::new(&env, &env.register(UltimateLiquidityContract, ()));
+        liquidity.initialize(&admin);
+        liquidity.set_lp_token_wasm(&admin, &soroban_sdk::BytesN::from_array(&env, &[0; 32]));
+        let swap = SwapContractClient::new(&env, &env.register(SwapContract, ()));
+        swap.initialize(&admin, &liquidity.address);
+        liquidity.set_swap_contract(&admin, &swap.address);
+
+        let token_a = env.register_stellar_asset_contract_v2(admin.clone()).address();
+        let token_b = env.register_stellar_asset_contract_v2(admin.clone()).address();
+        let reward = env.register_stellar_asset_contract_v2(admin.clone()).address();
+
+        let mut setup = Setup {
+            vault: VaultContractClient::new(&env, &env.register(VaultContract, ())),
+            pool_id: String::from_str(&env, ""),
+            env,
+            admin,
+            liquidity,
+            token_a,
+            token_b,
+            reward,
+        };
+        setup.pool_id = setup.seed(&swap, &setup.token_a, &setup.token_b);
+        setup.seed(&swap, &setup.reward, &setup.token_a);
+        swap.set_route_bases(&setup.admin, &vec![&setup.env, setup.swap_token(&setup.token_a)]);
+        setup.vault.initialize(&setup.admin, &setup.liquidity.address, &swap.address, &setup.pool_id, &100);
+        setup
+    }
+
+    fn seed(&self, swap: &SwapContractClient, token_a: &Address, token_b: &Address) -> String {
+        let pool_id = swap.create_pool(&self.swap_token(token_a), &self.swap_token(token_b), &30);
+        let provider = Address::generate(&self.env);
+        self.mint(token_a, &provider, RESERVE);
+        self.mint(token_b, &provider, RESERVE);
+        self.liquidity.add_liquidity(&provider, &pool_id, &RESERVE, &RESERVE, &0, &0, &u64::MAX);
+        pool_id
+    }
+
+    fn swap_token(&self, address: &Address) -> swave_swap::Token {
+        let token = self.token(address);
+        swave_swap::Token { address: token.address, symbol: token.symbol, decimals: token.decimals }
+    }
+
+    fn token(&self, address: &Address) -> Token {
+        let client = TokenClient::new(&self.env, address);
+        Token { address: Some(address.clone()), symbol: client.symbol(), decimals: client.decimals() }
+    }
+
+    fn mint(&self, token: &Address, to: &Address, amount: i128) {
+        StellarAssetClient::new(&self.env, token).mint(to, &amount);
+    }
+
+    fn balance(&self, token: &Address, holder: &Address) -> i128 {
+        TokenClient::new(&self.env, token).balance(holder)
+    }
+
+    /// A user funded with `amount_a` and `amount_b` who deposits all of it
+    fn deposit(&self, amount_a: i128, amount_b: i128) -> (Address, VaultDeposit) {
+        let user = Address::generate(&self.env);
+        self.mint(&self.token_a, &user, amount_a);
+        self.mint(&self.token_b, &user, amount_b);
+        let deposit = self.vault.deposit(&user, &amount_a, &amount_b, &0, &0, &u64::MAX);
+        (user, deposit)
+    }
+
+    fn staked(&self) -> i128 {
+        self.liquidity.get_staked_shares(&self.vault.address, &self.pool_id)
+    }
+
+    fn assert_reconciled(&self) {
+        for token in [&self.token_a, &self.token_b, &self.reward] {
+            let reconciliation = self.liquidity.reconcile_reserves(token);
+            assert_eq!(reconciliation.tracked_reserves, reconciliation.actual_balance);
+        }
+    }
+}
+
+#[test]
+fn test_initialize_twice() {
+    let setup = Setup::new();
+    let config = setup.vault.get_config();
+
+    assert_eq!(
+        setup.vault.try_initialize(&setup.admin, &config.liquidity_contract, &config.swap_contract, &setup.pool_id, &100),
+        Err(Ok(VaultError::AlreadyInitialized))
+    );
+    assert_eq!(setup.vault.try_set_bounty(&setup.admin, &501), Err(Ok(VaultError::InvalidConfig)));
+    assert_eq!(
+        setup.vault.try_add_reward_token(&setup.admin, &setup.token(&setup.token_a)),
+        Err(Ok(VaultError::InvalidConfig))
+    );
+}
+
+#[test]
+fn test_first_deposit_locks_minimum_shares() {
+    let setup = Setup::new();
+
+    // Only the amounts matching the pool ratio are taken
+    let (user, deposit) = setup.deposit(DEPOSIT, DEPOSIT + 200_000_000);
+    assert_eq!((deposit.amount_a, deposit.amount_b), (DEPOSIT, DEPOSIT));
+    assert_eq!(setup.balance(&setup.token_b, &user), 200_000_000);
+
+    let staked = setup.staked();
+    assert_eq!(deposit.shares, staked - MINIMUM_SHARES);
+    assert_eq!(setup.vault.get_state().total_shares, staked);
+    assert_eq!(setup.vault.get_shares(&user), deposit.shares);
+    assert_eq!(setup.vault.get_user_lp_tokens(&user), deposit.shares);
+
+    assert_eq!(
+        setup.vault.try_withdraw(&user, &(deposit.shares + 1), &0, &0, &u64::MAX),
+        Err(Ok(VaultError::InsufficientShares))
+    );
+    let (amount_a, amount_b) = setup.vault.withdraw(&user, &deposit.shares, &0, &0, &u64::MAX);
+    assert!(amount_a < DEPOSIT && amount_a > DEPOSIT - 2 * MINIMUM_SHARES);
+    assert!(amount_b < DEPOSIT && amount_b > DEPOSIT - 2 * MINIMUM_SHARES);
+
+    // The locked shares keep their LP behind them
+    assert_eq!(setup.vault.get_state().total_shares, MINIMUM_SHARES);
+    assert_eq!(setup.staked(), MINIMUM_SHARES);
+    setup.assert_reconciled();
+}
+
+#[test]
+fn test_idle_tokens_priced_into_shares() {
+    let setup = Setup::new();
+    let (first, _) = setup.deposit(DEPOSIT, DEPOSIT);
+
+    // Tokens sitting in the vault belong to the existing shares
+    let donation = 100_000_000;
+    setup.mint(&setup.token_a, &setup.vault.address, donation);
+    setup.mint(&setup.token_b, &setup.vault.address, donation);
+
+    let (second, deposit) = setup.deposit(DEPOSIT, DEPOSIT);
+    let shares = setup.vault.get_shares(&second);
+    let (amount_a, amount_b) = setup.vault.withdraw(&second, &shares, &0, &0, &u64::MAX);
+    assert_eq!(shares, deposit.shares);
+    assert!(amount_a <= DEPOSIT && amount_a > DEPOSIT - DEPOSIT / 10_000);
+    assert!(amount_b <= DEPOSIT && amount_b > DEPOSIT - DEPOSIT / 10_000);
+
+    let shares = setup.vault.get_shares(&first);
+    let (amount_a, amount_b) = setup.vault.withdraw(&first, &shares, &0, &0, &u64::MAX);
+    assert!(amount_a > DEPOSIT + donation - DEPOSIT / 10_000);
+    assert!(amount_b > DEPOSIT + donation - DEPOSIT / 10_000);
+    setup.assert_reconciled();
+}
+
+#[test]
+fn test_harvest_compounds_rewards() {
+    let setup = Setup::new();
+    let (user, deposit) = setup.deposit(DEPOSIT, DEPOSIT);
+    setup.vault.add_reward_token(&setup.admin, &setup.token(&setup.reward));
+
+    // A partner campaign paying the reward token to stakers, of which the vault is the only one
+    let partner = Address::generate(&setup.env);
+    let campaign = 100_000_000;
+    setup.mint(&setup.reward, &partner, campaign);
+    setup.liquidity.set_campaign_token(&setup.admin, &setup.reward, &1);
+    setup.liquidity.create_campaign(&partner, &setup.pool_id, &setup.reward, &campaign, &0, &100);
+    setup.env.ledger().with_mut(|ledger| ledger.timestamp = 200);
+
+    let keeper = Address::generate(&setup.env);
+    assert_eq!(setup.vault.try_harvest(&keeper), Err(Ok(VaultError::Unauthorized)));
+    setup.vault.set_keeper(&setup.admin, &keeper, &true);
+
+    let report = setup.vault.harvest(&keeper);
+    let claimed = report.rewards.get_unchecked(0).amount;
+    assert!(claimed > campaign - campaign / 1_000);
+    assert_eq!(setup.balance(&setup.reward, &keeper), claimed / 100);
+    assert_eq!(setup.balance(&setup.reward, &setup.vault.address), 0);
+    assert!(report.lp_tokens_added > 0);
+
+    let state = setup.vault.get_state();
+    assert_eq!((state.harvest_count, state.lp_compounded), (1, report.lp_tokens_added));
+    assert!(setup.vault.get_user_lp_tokens(&user) > deposit.shares);
+
+    setup.vault.set_keeper(&setup.admin, &keeper, &false);
+    assert_eq!(setup.vault.try_harvest(&keeper), Err(Ok(VaultError::Unauthorized)));
+
+    // Compounded rewards are paid out on withdrawal
+    let (amount_a, amount_b) = setup.vault.withdraw(&user, &deposit.shares, &0, &0, &u64::MAX);
+    assert!(amount_a > DEPOSIT && amount_b > DEPOSIT);
+    setup.assert_reconciled();
+}
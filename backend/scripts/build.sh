@@ -42,8 +42,8 @@ fi
 print_status "Cleaning previous builds..."
 cargo clean
 
-# Build each contract - All 9 contracts for complete testnet deployment
-contracts=("oracle" "multisig" "storage-manager" "credit-score" "loan" "collateral" "swap" "fee-manager" "liquidity")
+# Build each contract - All 11 contracts for complete testnet deployment
+contracts=("oracle" "multisig" "storage-manager" "credit-score" "loan" "collateral" "swap" "fee-manager" "liquidity" "lp-token" "vault")
 
 for contract in "${contracts[@]}"; do
     print_status "Building $contract contract..."
@@ -37,6 +37,11 @@ NETWORK=${1:-testnet}
 RPC_URL=""
 NETWORK_PASSPHRASE=""
 
+# Auto-compounding vault: the pool it compounds must already exist on the swap contract
+VAULT_POOL_ID=${VAULT_POOL_ID:-}
+VAULT_KEEPER=${VAULT_KEEPER:-}
+VAULT_BOUNTY_BPS=${VAULT_BOUNTY_BPS:-100}
+
 # Set network configuration
 case $NETWORK in
     "testnet")
@@ -104,8 +109,8 @@ cat > $ADDRESSES_FILE << EOF
 EOF
 
 # Contract deployment order (important for dependencies)
-# For testnet deployment: Include all 9 contracts with proper dependencies
-contracts=("oracle" "multisig" "storage-manager" "fee-manager" "collateral" "credit-score" "loan" "swap" "liquidity")
+# For testnet deployment: Include all 10 contracts with proper dependencies
+contracts=("oracle" "multisig" "storage-manager" "fee-manager" "collateral" "credit-score" "loan" "swap" "liquidity" "vault")
 
 declare -A contract_addresses
 
@@ -238,7 +243,8 @@ soroban contract invoke \
     --rpc-url $RPC_URL \
     --network-passphrase "$NETWORK_PASSPHRASE" \
     -- initialize \
-    --admin $DEPLOYER_ADDRESS
+    --admin $DEPLOYER_ADDRESS \
+    --liquidity_contract ${contract_addresses["liquidity"]}
 
 # Initialize Liquidity Contract
 print_status "Initializing Liquidity Contract..."
@@ -251,6 +257,109 @@ soroban contract invoke \
     -- initialize \
     --admin $DEPLOYER_ADDRESS
 
+# Let the Swap Contract settle trades against liquidity pool reserves
+soroban contract invoke \
+    --id ${contract_addresses["liquidity"]} \
+    --source $DEPLOYER_IDENTITY \
+    --network $NETWORK \
+    --rpc-url $RPC_URL \
+    --network-passphrase "$NETWORK_PASSPHRASE" \
+    -- set_swap_contract \
+    --caller $DEPLOYER_ADDRESS \
+    --swap_contract ${contract_addresses["swap"]}
+
+# Upload the LP token wasm that the Liquidity Contract deploys for each new pool
+print_status "Uploading LP Token wasm..."
+LP_TOKEN_WASM_HASH=$(soroban contract install \
+    --wasm contracts/lp-token/target/wasm32-unknown-unknown/release/swave_lp_token.wasm \
+    --source $DEPLOYER_IDENTITY \
+    --network $NETWORK \
+    --rpc-url $RPC_URL \
+    --network-passphrase "$NETWORK_PASSPHRASE")
+
+soroban contract invoke \
+    --id ${contract_addresses["liquidity"]} \
+    --source $DEPLOYER_IDENTITY \
+    --network $NETWORK \
+    --rpc-url $RPC_URL \
+    --network-passphrase "$NETWORK_PASSPHRASE" \
+    -- set_lp_token_wasm \
+    --caller $DEPLOYER_ADDRESS \
+    --wasm_hash $LP_TOKEN_WASM_HASH
+
+# Let the Liquidity Contract value positions with oracle prices
+soroban contract invoke \
+    --id ${contract_addresses["liquidity"]} \
+    --source $DEPLOYER_IDENTITY \
+    --network $NETWORK \
+    --rpc-url $RPC_URL \
+    --network-passphrase "$NETWORK_PASSPHRASE" \
+    -- set_oracle \
+    --caller $DEPLOYER_ADDRESS \
+    --oracle ${contract_addresses["oracle"]}
+
+# Let the Swap Contract check pool price guards against the oracle
+soroban contract invoke \
+    --id ${contract_addresses["swap"]} \
+    --source $DEPLOYER_IDENTITY \
+    --network $NETWORK \
+    --rpc-url $RPC_URL \
+    --network-passphrase "$NETWORK_PASSPHRASE" \
+    -- set_oracle \
+    --caller $DEPLOYER_ADDRESS \
+    --oracle ${contract_addresses["oracle"]}
+
+# Let the Swap Contract apply fee manager tier discounts
+soroban contract invoke \
+    --id ${contract_addresses["swap"]} \
+    --source $DEPLOYER_IDENTITY \
+    --network $NETWORK \
+    --rpc-url $RPC_URL \
+    --network-passphrase "$NETWORK_PASSPHRASE" \
+    -- set_fee_manager \
+    --caller $DEPLOYER_ADDRESS \
+    --fee_manager ${contract_addresses["fee-manager"]}
+
+# Initialize the Vault against its pool, approve its keeper, then turn on the harvest bounty
+if [ -n "$VAULT_POOL_ID" ]; then
+    print_status "Initializing Vault Contract for pool $VAULT_POOL_ID..."
+    soroban contract invoke \
+        --id ${contract_addresses["vault"]} \
+        --source $DEPLOYER_IDENTITY \
+        --network $NETWORK \
+        --rpc-url $RPC_URL \
+        --network-passphrase "$NETWORK_PASSPHRASE" \
+        -- initialize \
+        --admin $DEPLOYER_ADDRESS \
+        --liquidity_contract ${contract_addresses["liquidity"]} \
+        --swap_contract ${contract_addresses["swap"]} \
+        --pool_id "$VAULT_POOL_ID" \
+        --bounty_bps 0
+    
+    soroban contract invoke \
+        --id ${contract_addresses["vault"]} \
+        --source $DEPLOYER_IDENTITY \
+        --network $NETWORK \
+        --rpc-url $RPC_URL \
+        --network-passphrase "$NETWORK_PASSPHRASE" \
+        -- set_keeper \
+        --caller $DEPLOYER_ADDRESS \
+        --keeper ${VAULT_KEEPER:-$DEPLOYER_ADDRESS} \
+        --approved true
+    
+    soroban contract invoke \
+        --id ${contract_addresses["vault"]} \
+        --source $DEPLOYER_IDENTITY \
+        --network $NETWORK \
+        --rpc-url $RPC_URL \
+        --network-passphrase "$NETWORK_PASSPHRASE" \
+        -- set_bounty \
+        --caller $DEPLOYER_ADDRESS \
+        --bounty_bps $VAULT_BOUNTY_BPS
+else
+    print_warning "VAULT_POOL_ID not set; Vault deployed but not initialized"
+fi
+
 print_success "All contracts initialized successfully!"
 
 # Testnet-specific configuration